@@ -0,0 +1,225 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io, mem,
+    os::{fd::AsRawFd, unix::net::UnixStream},
+};
+
+use crate::subprocess::SocketEvent;
+
+use serde::{Deserialize, Serialize};
+
+// Every level includes the ones before it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccessLevel {
+    // Application information, command history, health and output.
+    ReadOnly,
+    // Sending commands to stdin.
+    Operator,
+    // Stopping the application.
+    Admin,
+}
+
+impl Display for AccessLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessLevel::ReadOnly => write!(f, "read-only"),
+            AccessLevel::Operator => write!(f, "operator"),
+            AccessLevel::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+// Users and groups allowed to use the application socket, by uid and gid.
+// The user running the supervisor and root are always admins.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AccessControl {
+    #[serde(default)]
+    pub users: BTreeMap<u32, AccessLevel>,
+    // Only the primary group of a client is known.
+    #[serde(default)]
+    pub groups: BTreeMap<u32, AccessLevel>,
+}
+
+impl AccessControl {
+    // The highest level given to the user or its group.
+    pub fn level(&self, uid: u32, gid: u32) -> Option<AccessLevel> {
+        self.users.get(&uid).max(self.groups.get(&gid)).copied()
+    }
+}
+
+impl Display for AccessControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let users = self
+            .users
+            .iter()
+            .map(|(uid, level)| format!("uid {uid} {level}"));
+
+        let groups = self
+            .groups
+            .iter()
+            .map(|(gid, level)| format!("gid {gid} {level}"));
+
+        let entries: Vec<String> = users.chain(groups).collect();
+        write!(f, "{}", entries.join(", "))
+    }
+}
+
+// The client on the other end of a connection.
+pub struct Peer {
+    pub name: String,
+    // None if the client can only check if the application is running.
+    pub level: Option<AccessLevel>,
+}
+
+impl Peer {
+    // Remote clients authenticated with the token, which gives full access.
+    pub fn remote(address: &str) -> Peer {
+        Peer {
+            name: format!("remote client from {address}"),
+            level: Some(AccessLevel::Admin),
+        }
+    }
+
+    pub fn unix(stream: &UnixStream, access: Option<&AccessControl>) -> io::Result<Peer> {
+        let (uid, gid) = peer_credentials(stream)?;
+        let owner = unsafe { libc::geteuid() };
+
+        let level = match uid == owner || uid == 0 {
+            true => Some(AccessLevel::Admin),
+            false => access.and_then(|access| access.level(uid, gid)),
+        };
+
+        Ok(Peer {
+            name: format!("uid {uid} (gid {gid})"),
+            level,
+        })
+    }
+
+    // Returns an error with the reason if the client can't send the event.
+    pub fn check(&self, event: &SocketEvent) -> Result<(), String> {
+        let (request, required) = match required_level(event) {
+            Some(required) => required,
+            None => return Ok(()),
+        };
+
+        if self.level >= Some(required) {
+            return Ok(());
+        }
+
+        Err(format!(
+            "Permission denied, '{request}' requires {required} access."
+        ))
+    }
+}
+
+// Checking if the application is running and its version is open to anyone able to connect.
+fn required_level(event: &SocketEvent) -> Option<(&'static str, AccessLevel)> {
+    match event {
+        SocketEvent::Ping | SocketEvent::Hello(_) | SocketEvent::Auth(_) => None,
+        SocketEvent::RetrieveAppInfo(_) => Some(("status", AccessLevel::ReadOnly)),
+        SocketEvent::CommandHistory(_) => Some(("history", AccessLevel::ReadOnly)),
+        SocketEvent::Health(_) => Some(("health", AccessLevel::ReadOnly)),
+        SocketEvent::Subscribe => Some(("subscribe", AccessLevel::ReadOnly)),
+        SocketEvent::WriteStdin(_) => Some(("send", AccessLevel::Operator)),
+        SocketEvent::Capture { .. } => Some(("send", AccessLevel::Operator)),
+        SocketEvent::Stop { .. } => Some(("stop", AccessLevel::Admin)),
+        // Answered with an error anyway.
+        SocketEvent::Stopped(_)
+        | SocketEvent::Output(_)
+        | SocketEvent::Captured { .. }
+        | SocketEvent::Ack
+        | SocketEvent::Error(_) => None,
+    }
+}
+
+// Credentials of the process that connected, as checked by the kernel.
+fn peer_credentials(stream: &UnixStream) -> io::Result<(u32, u32)> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+
+    let mut length = mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok((credentials.uid, credentials.gid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::StoppedBy;
+
+    #[test]
+    fn unit_access_control_level() -> anyhow::Result<()> {
+        let access: AccessControl = serde_json::from_str(
+            r#"{"users": {"1001": "operator", "1002": "read-only"}, "groups": {"100": "admin"}}"#,
+        )?;
+
+        assert_eq!(access.level(1001, 1001), Some(AccessLevel::Operator));
+        assert_eq!(access.level(1002, 100), Some(AccessLevel::Admin));
+        assert_eq!(access.level(1003, 1003), None);
+        assert_eq!(
+            access.to_string(),
+            "uid 1001 operator, uid 1002 read-only, gid 100 admin"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unit_access_peer_check() -> anyhow::Result<()> {
+        let stop = SocketEvent::Stop {
+            force: false,
+            stopped_by: StoppedBy::Stop,
+            timeout: None,
+        };
+
+        let peer = Peer {
+            name: String::from("uid 1001 (gid 1001)"),
+            level: Some(AccessLevel::Operator),
+        };
+
+        assert!(peer.check(&SocketEvent::Subscribe).is_ok());
+        assert!(peer
+            .check(&SocketEvent::WriteStdin(String::from("op")))
+            .is_ok());
+        assert_eq!(
+            peer.check(&stop),
+            Err(String::from(
+                "Permission denied, 'stop' requires admin access."
+            ))
+        );
+
+        let peer = Peer {
+            name: String::from("uid 1003 (gid 1003)"),
+            level: None,
+        };
+
+        assert!(peer.check(&SocketEvent::Ping).is_ok());
+        assert!(peer
+            .check(&SocketEvent::RetrieveAppInfo(Box::default()))
+            .is_err());
+
+        // Both ends belong to this process, which is running the supervisor in this case.
+        let (stream, _) = UnixStream::pair()?;
+        let peer = Peer::unix(&stream, None)?;
+        assert_eq!(peer.level, Some(AccessLevel::Admin));
+        Ok(())
+    }
+}
@@ -1,15 +1,31 @@
 use std::{
+    collections::BTreeMap,
     fs,
-    io::{Read, Write},
+    io::ErrorKind,
     os::unix::net::UnixStream,
     path::PathBuf,
     str::FromStr,
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::{commands::start::StartArgs, crescent, subprocess::SocketEvent};
+use crate::{
+    access::AccessControl,
+    client::Subscription,
+    commands::start::StartArgs,
+    crescent,
+    error::{Error, Result},
+    health::{HealthCheck, HealthStatus, Readiness},
+    history::StoppedBy,
+    remote::{self, RemoteListener},
+    schedule::ScheduledRestart,
+    socket::{self, Hello, Message, Stream},
+    subprocess::{SocketEvent, StopStep},
+};
 
-use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use subprocess::ExitStatus;
 use sysinfo::Pid;
 
 #[derive(Serialize, Deserialize, Clone, Default)]
@@ -17,14 +33,175 @@ pub struct Application {
     pub name: String,
     pub file_path: PathBuf,
     pub stop_command: Option<String>,
+    pub stop_timeout: Option<u64>,
+    pub health_check: Option<HealthCheck>,
+    pub readiness: Option<Readiness>,
+    pub schedules: Option<BTreeMap<String, String>>,
+    pub scheduled_restart: Option<ScheduledRestart>,
+    pub remote: Option<RemoteListener>,
+    pub access: Option<AccessControl>,
+    pub depends_on: Option<Vec<String>>,
+    pub labels: Option<Vec<String>>,
+    // Resolved when starting, restarts and saved applications use the same values.
+    pub env: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    pub clean_env: bool,
+    pub path: Option<String>,
     pub cmd: Vec<String>,
     pub start_args: StartArgs,
+    // Arguments given with a profile, before the profile fields were merged.
+    #[serde(default)]
+    pub cli_args: Option<StartArgs>,
+}
+
+impl Application {
+    // The subprocess and health check commands run in the directory of the executable.
+    pub fn work_dir(&self) -> PathBuf {
+        self.file_path.parent().unwrap().to_path_buf()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    OnFailure,
+    Always,
+}
+
+impl RestartPolicy {
+    pub fn should_restart(&self, status: &ExitStatus) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !status.success(),
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FailedState {
+    pub reason: String,
+    // Unix timestamp in seconds.
+    pub failed_at: u64,
+    pub last_lines: Vec<String>,
+}
+
+// Resolves names, names separated by commas, glob patterns like 'lobby-*' and labels.
+// Patterns, labels and `all` only select running applications.
+pub fn resolve_app_names(patterns: &[String], all: bool, labels: &[String]) -> Result<Vec<String>> {
+    let patterns: Vec<String> = patterns
+        .iter()
+        .flat_map(|pattern| pattern.split(','))
+        .filter(|pattern| !pattern.trim().is_empty())
+        .map(|pattern| pattern.trim().to_string())
+        .collect();
+
+    let running = if remote::get().is_some() {
+        vec![remote_app_name()?]
+    } else if all || !labels.is_empty() || patterns.iter().any(|pattern| is_glob(pattern)) {
+        crescent::get_app_names()?
+            .into_iter()
+            .filter(|name| app_already_running(name).unwrap_or(false))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let mut names: Vec<String> = if all { running.clone() } else { vec![] };
+
+    for label in labels {
+        let labeled: Vec<&String> = running
+            .iter()
+            .filter(|name| has_label(name, label))
+            .collect();
+
+        if labeled.is_empty() {
+            return Err(Error::NoLabeledApp(label.clone()));
+        }
+
+        for name in labeled {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+
+    for pattern in patterns {
+        if !is_glob(&pattern) {
+            if !names.contains(&pattern) {
+                names.push(pattern);
+            }
+            continue;
+        }
+
+        let matched: Vec<&String> = running
+            .iter()
+            .filter(|name| matches_glob(&pattern, name))
+            .collect();
+
+        if matched.is_empty() {
+            return Err(Error::NoMatchingApp(pattern));
+        }
+
+        for name in matched {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+fn has_label(name: &String, label: &String) -> bool {
+    match get_app_info(name) {
+        Ok(app_info) => app_info.labels.iter().flatten().any(|other| other == label),
+        Err(_) => false,
+    }
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+// Supports '*' for any sequence of characters and '?' for a single character.
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
 }
 
 pub fn check_app_exists(name: &String) -> Result<PathBuf> {
+    // A remote listener only serves its own application.
+    if remote::get().is_some() && remote_app_name()? != *name {
+        return Err(Error::AppNotFound);
+    }
+
     let app_dir = app_dir_by_name(name)?;
-    if !app_dir.exists() {
-        return Err(anyhow!("Application does not exist."));
+    if !app_dir.exists() && remote::get().is_none() {
+        return Err(Error::AppNotFound);
     }
     Ok(app_dir)
 }
@@ -42,111 +219,251 @@ pub fn get_app_socket(name: &String) -> Result<PathBuf> {
     Ok(socket_dir)
 }
 
-pub fn app_pids_by_name(name: &String) -> Result<Vec<Pid>> {
-    let mut application_path = app_dir_by_name(name)?;
+pub fn get_app_failed_state(name: &String) -> Result<Option<FailedState>> {
+    let mut failed_path = app_dir_by_name(name)?;
+    failed_path.push(format!("{}.failed", name));
 
-    let app_name = application_path
-        .file_name()
-        .context("Error extracting file name.")?
-        .to_str()
-        .context("Error converting OsStr to str.")?
-        .to_string();
+    if !failed_path.exists() {
+        return Ok(None);
+    }
+
+    let file =
+        fs::File::open(failed_path).map_err(Error::io("Error opening failed state file."))?;
+    let state: FailedState = serde_json::from_reader(file)
+        .map_err(|_| Error::Corrupted(String::from("Error reading failed state file.")))?;
+
+    Ok(Some(state))
+}
 
-    application_path.push(app_name + ".pid");
+pub fn app_pids_by_name(name: &String) -> Result<Vec<Pid>> {
+    let mut application_path = app_dir_by_name(name)?;
+    application_path.push(format!("{name}.pid"));
 
     if !application_path.exists() {
         return Ok(vec![]);
     }
 
-    let pid_file =
-        fs::read_to_string(application_path).context("Error reading PID file to string.")?;
+    let pid_file = fs::read_to_string(application_path)
+        .map_err(Error::io("Error reading PID file to string."))?;
 
     let mut pid_strs: Vec<&str> = pid_file.split('\n').collect();
     pid_strs.retain(|&x| !x.is_empty());
 
-    if pid_strs.is_empty() {
-        return Ok(vec![]);
+    let parse = |pid: &str| {
+        Pid::from_str(pid).map_err(|_| Error::Corrupted(format!("Error parsing PID {pid}")))
+    };
+
+    pid_strs.into_iter().take(2).map(parse).collect()
+}
+
+pub fn app_already_running(name: &String) -> Result<bool> {
+    if remote::get().is_none() && app_pids_by_name(name)?.len() < 2 {
+        return Ok(false);
     }
 
-    let cres_pid =
-        Pid::from_str(pid_strs[0]).with_context(|| format!("Error parsing PID {}", pid_strs[0]))?;
+    // A supervisor that was killed does not delete its socket file,
+    // connecting to it fails the same way as when there is no socket.
+    // One from an older version never answers, but it is still running.
+    match ping_app(name) {
+        Ok(_) | Err(Error::Unsupported { .. }) => Ok(true),
+        Err(Error::Connection { .. }) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
 
-    if pid_strs.len() == 1 {
-        return Ok(vec![cres_pid]);
+pub fn connect_app(name: &String) -> Result<Stream> {
+    if let Some(remote) = remote::get() {
+        return remote.connect();
     }
 
-    let app_pid =
-        Pid::from_str(pid_strs[1]).with_context(|| format!("Error parsing PID {}", pid_strs[1]))?;
+    let socket_dir = get_app_socket(name)?;
 
-    let pids: Vec<Pid> = vec![cres_pid, app_pid];
+    let stream = UnixStream::connect(socket_dir).map_err(|err| Error::Connection {
+        target: format!("'{name}' socket"),
+        source: Some(Box::new(err)),
+    })?;
 
-    Ok(pids)
+    Ok(Stream::Unix(stream))
 }
 
-pub fn app_already_running(name: &String) -> Result<bool> {
-    match app_pids_by_name(name) {
-        Ok(pids) => {
-            if pids.is_empty() || pids.len() < 2 {
-                return Ok(false);
-            }
+// Name of the application served by the remote listener.
+pub fn remote_app_name() -> Result<String> {
+    Ok(get_app_info(&String::new())?.name)
+}
 
-            match ping_app(name) {
-                Ok(_) => Ok(true),
-                Err(err) => {
-                    // This looks horrible
-                    // If the error contains 'Error connecting to'
-                    // it means that an error from socket has occurred.
-                    // This might happen if you kill the crescent process
-                    // which will not delete the socket file, and will
-                    // error out if you try connecting to it.
-                    if err.to_string().contains("Error connecting to") {
-                        return Ok(false);
-                    }
-                    Err(err)
-                }
-            }
+// Sends an event without waiting for the response.
+pub fn send_event(name: &String, event: SocketEvent) -> Result<()> {
+    let mut stream = connect_app(name)?;
+    socket::write_event(&mut stream, &Message::new(event))
+}
+
+// Sends an event and waits for the response, an `Error` response is returned as an error.
+pub fn request_event(name: &String, event: SocketEvent) -> Result<SocketEvent> {
+    let mut stream = connect_app(name)?;
+
+    match exchange(&mut stream, event)? {
+        Some(SocketEvent::Error(err)) => Err(Error::Application(err)),
+        Some(event) => Ok(event),
+        None => Err(Error::Protocol(String::from(
+            "Connection closed before receiving a response.",
+        ))),
+    }
+}
+
+// Calls `on_line` with every new line of the application output until the connection is closed
+// or `on_line` returns false.
+pub fn follow_output<F: FnMut(String) -> bool>(name: &String, mut on_line: F) -> Result<()> {
+    require_capability(name, socket::CAPABILITY_OUTPUT)?;
+
+    for line in Subscription::open(name)? {
+        if !on_line(line?) {
+            break;
         }
-        Err(err) => Err(err),
     }
+
+    Ok(())
 }
 
-pub fn get_app_info(name: &String) -> Result<Application> {
-    let socket_dir = get_app_socket(name)?;
+// Writes the event and reads messages until the one answering it,
+// returns None if the connection was closed before that.
+pub fn exchange(stream: &mut Stream, event: SocketEvent) -> Result<Option<SocketEvent>> {
+    let request = Message::new(event);
+    socket::write_event(stream, &request)?;
 
-    let mut stream = UnixStream::connect(socket_dir)
-        .context(format!("Error connecting to '{}' socket.", name))?;
+    while let Some(message) = socket::read_event::<_, Message>(stream)? {
+        if message.id == request.id || message.id == socket::UNKNOWN_REQUEST_ID {
+            return Ok(Some(message.event));
+        }
+    }
 
-    let event = serde_json::to_vec(&SocketEvent::RetrieveAppInfo(Box::default()))?;
+    Ok(None)
+}
 
-    stream.write_all(&event)?;
+// Asks the supervisor to stop the subprocess, returning after it exits.
+pub fn stop_app(name: &String, force: bool, timeout: Option<u64>) -> Result<StopStep> {
+    match request_event(name, stop_event(force, timeout))? {
+        SocketEvent::Stopped(step) => Ok(step),
+        _ => Err(Error::unexpected_response()),
+    }
+}
 
-    let mut received = vec![0u8; 1024];
+// The supervisor sends the signal, this way it knows not to restart the subprocess.
+pub fn stop_event(force: bool, timeout: Option<u64>) -> SocketEvent {
+    SocketEvent::Stop {
+        force,
+        stopped_by: StoppedBy::Stop,
+        timeout,
+    }
+}
 
-    loop {
-        let read = stream.read(&mut received)?;
-        if read > 0 {
-            return Ok(serde_json::from_slice::<Application>(&received[..read])?);
-        }
+// Older supervisors don't answer the handshake or any framed event,
+// waiting for a response only for this long.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn get_app_hello(name: &String) -> Result<Hello> {
+    let mut stream = connect_app(name)?;
+    stream.set_read_timeout(Some(HELLO_TIMEOUT))?;
+
+    match exchange(&mut stream, SocketEvent::Hello(Hello::current())) {
+        Ok(Some(SocketEvent::Hello(hello))) => Ok(hello),
+        _ => Ok(Hello::legacy()),
     }
 }
 
-fn ping_app(name: &String) -> Result<SocketEvent> {
-    let socket_dir = get_app_socket(name)?;
+// Returns an error explaining the application has to be restarted to use a capability.
+pub fn require_capability(name: &String, capability: &str) -> Result<()> {
+    let hello = get_app_hello(name)?;
+
+    if hello.supports(capability) {
+        return Ok(());
+    }
+
+    Err(unsupported(name, &hello, capability))
+}
+
+fn unsupported(name: &str, hello: &Hello, capability: &str) -> Error {
+    let version = match hello.version.as_str() {
+        "unknown" => String::from("an older crescent version"),
+        version => format!("crescent {version}"),
+    };
+
+    Error::Unsupported {
+        name: name.to_string(),
+        version,
+        capability: capability.to_string(),
+    }
+}
+
+fn is_timeout(err: &Error) -> bool {
+    matches!(err, Error::Io { source, .. }
+        if matches!(source.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut))
+}
+
+// Returns None if the application has no health check.
+pub fn get_app_health(name: &String) -> Result<Option<HealthStatus>> {
+    if !get_app_hello(name)?.supports(socket::CAPABILITY_HEALTH) {
+        return Ok(None);
+    }
+
+    match request_event(name, SocketEvent::Health(None))? {
+        SocketEvent::Health(health) => Ok(health),
+        _ => Err(Error::unexpected_response()),
+    }
+}
+
+pub fn wait_app_exit(name: &String, timeout: Duration) -> Result<()> {
+    let start = Instant::now();
+
+    while app_already_running(name)? {
+        if start.elapsed() >= timeout {
+            return Err(Error::Timeout(format!(
+                "Application did not exit after {} seconds.",
+                timeout.as_secs()
+            )));
+        }
 
-    let mut stream = UnixStream::connect(socket_dir)
-        .context(format!("Error connecting to '{}' socket.", name))?;
+        thread::sleep(Duration::from_millis(250));
+    }
 
-    let event = serde_json::to_vec(&SocketEvent::Ping)?;
+    Ok(())
+}
 
-    stream.write_all(&event)?;
+pub fn get_app_info(name: &String) -> Result<Application> {
+    match query_app(name, SocketEvent::RetrieveAppInfo(Box::default()), "status")? {
+        SocketEvent::RetrieveAppInfo(app_info) => Ok(*app_info),
+        _ => Err(Error::unexpected_response()),
+    }
+}
 
-    let mut received = vec![0u8; 1024];
+fn ping_app(name: &String) -> Result<SocketEvent> {
+    query_app(name, SocketEvent::Ping, "ping")
+}
 
-    loop {
-        let read = stream.read(&mut received)?;
-        if read > 0 {
-            return Ok(serde_json::from_slice::<SocketEvent>(&received[..read])?);
+// Like `request_event`, but any error reading the response is treated as the
+// application not running, the supervisor might close the connection while shutting down.
+// No response at all means it is from an older version, `Unsupported` is returned with `feature`.
+fn query_app(name: &String, event: SocketEvent, feature: &str) -> Result<SocketEvent> {
+    let mut stream = connect_app(name)?;
+    stream.set_read_timeout(Some(HELLO_TIMEOUT))?;
+
+    let response = match exchange(&mut stream, event) {
+        Ok(response) => response,
+        Err(err) if is_timeout(&err) => return Err(unsupported(name, &Hello::legacy(), feature)),
+        Err(err) => {
+            return Err(Error::Connection {
+                target: format!("'{name}' socket"),
+                source: Some(Box::new(err)),
+            })
         }
+    };
+
+    match response {
+        Some(SocketEvent::Error(err)) => Err(Error::Application(err)),
+        Some(event) => Ok(event),
+        None => Err(Error::Connection {
+            target: format!("'{name}' socket, connection closed"),
+            source: None,
+        }),
     }
 }
 
@@ -154,7 +471,64 @@ fn ping_app(name: &String) -> Result<SocketEvent> {
 mod tests {
     use super::*;
     use crate::crescent::crescent_dir;
-    use std::fs::remove_dir_all;
+    use std::{fs::remove_dir_all, io::Read, os::unix::net::UnixListener};
+
+    #[test]
+    fn unit_require_capability_legacy_supervisor() -> Result<()> {
+        let name = String::from("unit_require_capability");
+        let app_dir = app_dir_by_name(&name)?;
+        fs::create_dir_all(&app_dir)?;
+
+        // Supervisors from before the handshake read the event and never answer it.
+        let listener = UnixListener::bind(app_dir.join(name.clone() + ".sock"))?;
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.read(&mut [0u8; 1024]);
+                thread::sleep(Duration::from_secs(5));
+            }
+        });
+
+        assert_eq!(get_app_hello(&name)?, Hello::legacy());
+
+        let err = require_capability(&name, socket::CAPABILITY_STOP).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "'unit_require_capability' was started by an older crescent version, restart it to use 'stop'."
+        );
+
+        remove_dir_all(app_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unit_query_legacy_supervisor() -> Result<()> {
+        let name = String::from("unit_query_legacy_supervisor");
+        let app_dir = app_dir_by_name(&name)?;
+        fs::create_dir_all(&app_dir)?;
+
+        let listener = UnixListener::bind(app_dir.join(name.clone() + ".sock"))?;
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.read(&mut [0u8; 1024]);
+                thread::sleep(Duration::from_secs(5));
+            }
+        });
+
+        let start = Instant::now();
+        assert!(matches!(ping_app(&name), Err(Error::Unsupported { .. })));
+
+        let Err(err) = get_app_info(&name) else {
+            panic!("Expected an error from the older supervisor.");
+        };
+        assert_eq!(
+            format!("{}", err),
+            "'unit_query_legacy_supervisor' was started by an older crescent version, restart it to use 'status'."
+        );
+        assert!(start.elapsed() < HELLO_TIMEOUT * 3);
+
+        remove_dir_all(app_dir)?;
+        Ok(())
+    }
 
     #[test]
     fn unit_app_dir_by_name() -> Result<()> {
@@ -167,4 +541,49 @@ mod tests {
         remove_dir_all(home_path)?;
         Ok(())
     }
+
+    #[test]
+    fn unit_matches_glob() {
+        assert!(matches_glob("lobby-*", "lobby-1"));
+        assert!(matches_glob("lobby-*", "lobby-"));
+        assert!(matches_glob("*-1", "lobby-1"));
+        assert!(matches_glob("l?bby-*1", "lobby-21"));
+        assert!(matches_glob("*", "proxy"));
+        assert!(!matches_glob("lobby-*", "proxy"));
+        assert!(!matches_glob("lobby-?", "lobby-10"));
+    }
+
+    #[test]
+    fn unit_resolve_app_names() -> Result<()> {
+        let names = vec![String::from("a,b"), String::from("c"), String::from("a")];
+        assert_eq!(resolve_app_names(&names, false, &[])?, vec!["a", "b", "c"]);
+
+        let err =
+            resolve_app_names(&[String::from("unit_resolve_app_names-*")], false, &[]).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "No running application matches 'unit_resolve_app_names-*'."
+        );
+
+        let err =
+            resolve_app_names(&[], false, &[String::from("unit_resolve_app_names")]).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "No running application has the label 'unit_resolve_app_names'."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unit_restart_policy_should_restart() {
+        let success = ExitStatus::Exited(0);
+        let failure = ExitStatus::Exited(1);
+        let signaled = ExitStatus::Signaled(9);
+
+        assert!(!RestartPolicy::Never.should_restart(&failure));
+        assert!(!RestartPolicy::OnFailure.should_restart(&success));
+        assert!(RestartPolicy::OnFailure.should_restart(&failure));
+        assert!(RestartPolicy::OnFailure.should_restart(&signaled));
+        assert!(RestartPolicy::Always.should_restart(&success));
+    }
 }
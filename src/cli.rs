@@ -0,0 +1,120 @@
+use std::{env, io, path::PathBuf};
+
+use crate::{
+    commands::{
+        api::ApiArgs,
+        attach::AttachArgs,
+        daemon::DaemonArgs,
+        history::HistoryArgs,
+        list::ListArgs,
+        log::LogArgs,
+        profile::ProfileArgs,
+        restart::RestartArgs,
+        save::SaveArgs,
+        send::SendArgs,
+        signals::{KillArgs, SignalArgs, StopArgs},
+        stack::StackArgs,
+        start::StartArgs,
+        startup::StartupArgs,
+        status::StatusArgs,
+    },
+    remote,
+};
+
+use Commands::*;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+
+#[derive(Parser)]
+#[command(name = "crescent", version, about)]
+struct Crescent {
+    #[command(subcommand)]
+    pub commands: Commands,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "HOST:PORT",
+        help = "Manage the application of a remote listener instead of local ones."
+    )]
+    pub remote: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        requires = "remote",
+        help = "File with the remote listener token, read from CRESCENT_TOKEN if not set."
+    )]
+    pub token_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    Start(StartArgs),
+    List(ListArgs),
+    Send(SendArgs),
+    Log(LogArgs),
+    Attach(AttachArgs),
+    Signal(SignalArgs),
+    Stop(StopArgs),
+    Restart(RestartArgs),
+    Kill(KillArgs),
+    Status(StatusArgs),
+    History(HistoryArgs),
+    Profile(ProfileArgs),
+    Save(SaveArgs),
+    Api(ApiArgs),
+    Daemon(DaemonArgs),
+    Startup(StartupArgs),
+    Stack(StackArgs),
+    #[command(about = "Print a completions file for the specified shell.")]
+    Complete {
+        shell: Shell,
+    },
+}
+
+// Parses the arguments and runs the command, the `cres` binary only calls this.
+pub fn run() -> Result<()> {
+    let cli = Crescent::parse();
+
+    if let Some(address) = cli.remote {
+        let token = match cli.token_file {
+            Some(path) => remote::read_token(&path)?,
+            None => env::var("CRESCENT_TOKEN")
+                .context("A token is required, use '--token-file' or CRESCENT_TOKEN.")?,
+        };
+
+        match cli.commands {
+            List(_) | Status(_) | Send(_) | Stop(_) | Attach(_) => {}
+            _ => return Err(anyhow!("Command not available with '--remote'.")),
+        }
+
+        remote::set(remote::Remote { address, token });
+    }
+
+    match cli.commands {
+        Start(args) => StartArgs::run(args),
+        List(args) => ListArgs::run(args),
+        Send(args) => SendArgs::run(args),
+        Log(args) => LogArgs::run(args),
+        Attach(args) => AttachArgs::run(args),
+        Signal(args) => SignalArgs::run(args),
+        Stop(args) => StopArgs::run(args),
+        Restart(args) => RestartArgs::run(args),
+        Status(args) => StatusArgs::run(args),
+        History(args) => HistoryArgs::run(args),
+        Kill(args) => KillArgs::run(args),
+        Profile(args) => ProfileArgs::run(args),
+        Save(args) => SaveArgs::run(args),
+        Api(args) => ApiArgs::run(args),
+        Daemon(args) => DaemonArgs::run(args),
+        Startup(args) => StartupArgs::run(args),
+        Stack(args) => StackArgs::run(args),
+        Complete { shell } => {
+            clap_complete::generate(shell, &mut Crescent::command(), "cres", &mut io::stdout());
+            Ok(())
+        }
+    }
+}
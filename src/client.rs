@@ -0,0 +1,132 @@
+use crate::{
+    application::{self, Application},
+    error::{Error, Result},
+    socket::{self, Hello, Message, Stream},
+    subprocess::{SocketEvent, StopStep},
+};
+
+// Typed access to a running application, the same requests the commands make.
+pub struct Client {
+    name: String,
+    hello: Hello,
+}
+
+impl Client {
+    // Fails if the application does not exist or is not running.
+    pub fn connect(name: &str) -> Result<Client> {
+        let name = name.to_string();
+        application::check_app_exists(&name)?;
+
+        if !application::app_already_running(&name)? {
+            return Err(Error::AppNotRunning);
+        }
+
+        let hello = application::get_app_hello(&name)?;
+        Ok(Client { name, hello })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Version and capabilities of the supervisor.
+    pub fn hello(&self) -> &Hello {
+        &self.hello
+    }
+
+    pub fn info(&self) -> Result<Application> {
+        application::get_app_info(&self.name)
+    }
+
+    // Writes the command to the application stdin.
+    pub fn send(&self, command: &str) -> Result<()> {
+        match application::request_event(&self.name, SocketEvent::WriteStdin(command.to_string()))?
+        {
+            SocketEvent::Ack => Ok(()),
+            _ => Err(Error::unexpected_response()),
+        }
+    }
+
+    // Uses the stop command if the application has one, then SIGTERM and SIGKILL.
+    pub fn stop(&self) -> Result<StopStep> {
+        self.stop_with(false, None)
+    }
+
+    // Like `stop`, `force` skips the stop command and starts with SIGTERM, then SIGKILL,
+    // and `timeout` overrides the seconds to wait for each step.
+    pub fn stop_with(&self, force: bool, timeout: Option<u64>) -> Result<StopStep> {
+        self.require(socket::CAPABILITY_STOP)?;
+        application::stop_app(&self.name, force, timeout)
+    }
+
+    // New lines of the application output, until it stops.
+    pub fn subscribe(&self) -> Result<Subscription> {
+        self.require(socket::CAPABILITY_OUTPUT)?;
+        Subscription::open(&self.name)
+    }
+
+    fn require(&self, capability: &str) -> Result<()> {
+        match self.hello.supports(capability) {
+            true => Ok(()),
+            false => application::require_capability(&self.name, capability),
+        }
+    }
+}
+
+// Iterator over the output lines sent to a subscribed connection.
+pub struct Subscription {
+    stream: Stream,
+    closed: bool,
+}
+
+impl Subscription {
+    // The capability has to be checked before.
+    pub(crate) fn open(name: &String) -> Result<Subscription> {
+        let mut stream = application::connect_app(name)?;
+
+        match application::exchange(&mut stream, SocketEvent::Subscribe)? {
+            Some(SocketEvent::Ack) => {}
+            Some(SocketEvent::Error(err)) => return Err(Error::Application(err)),
+            Some(_) => return Err(Error::unexpected_response()),
+            None => {
+                return Err(Error::Protocol(String::from(
+                    "Connection closed before receiving a response.",
+                )))
+            }
+        }
+
+        Ok(Subscription {
+            stream,
+            closed: false,
+        })
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.closed {
+            let event = match socket::read_event::<_, Message>(&mut self.stream) {
+                Ok(Some(message)) => message.event,
+                Ok(None) => break,
+                Err(err) => {
+                    self.closed = true;
+                    return Some(Err(err));
+                }
+            };
+
+            match event {
+                SocketEvent::Output(line) => return Some(Ok(line)),
+                SocketEvent::Error(err) => {
+                    self.closed = true;
+                    return Some(Err(Error::Application(err)));
+                }
+                _ => {}
+            }
+        }
+
+        self.closed = true;
+        None
+    }
+}
@@ -0,0 +1,644 @@
+use std::{
+    fs::{self, Permissions},
+    io::Write,
+    net::{IpAddr, ToSocketAddrs},
+    os::unix::{fs::PermissionsExt, net::UnixStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+};
+
+use super::{log, send, signals, start::StartArgs};
+use crate::{
+    application::{self, Application, FailedState},
+    crescent, environment,
+    error::Error,
+    health::HealthStatus,
+    output::Capture,
+    remote, tail, util,
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, ValueHint};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+#[derive(Args)]
+#[command(about = "Serve an HTTP/JSON API to manage applications from other tools.")]
+pub struct ApiArgs {
+    #[arg(
+        short,
+        long,
+        help = "Loopback address and port to listen on.",
+        default_value = "127.0.0.1:7600"
+    )]
+    pub listen: String,
+
+    #[arg(
+        long,
+        help = "Listen on a Unix socket at this path instead.",
+        value_hint = ValueHint::FilePath,
+        conflicts_with = "listen"
+    )]
+    pub socket: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "File with the token clients send as 'Authorization: Bearer <token>'. Required when listening on an address.",
+        value_hint = ValueHint::FilePath
+    )]
+    pub auth_token_file: Option<PathBuf>,
+}
+
+const DEFAULT_LOG_LINES: usize = 200;
+
+#[derive(Debug, PartialEq)]
+enum Route {
+    List,
+    Start,
+    Status(String),
+    Stop(String),
+    Kill(String),
+    Signal(String),
+    Send(String),
+    Log(String, usize),
+    Output(String),
+    NotFound,
+}
+
+#[derive(Deserialize)]
+struct StartRequest {
+    profile: String,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StopRequest {
+    #[serde(default)]
+    force: bool,
+    timeout: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SignalRequest {
+    signal: u8,
+}
+
+#[derive(Deserialize)]
+struct SendRequest {
+    command: String,
+    // Same as `send --wait`, responding with the lines that followed the command.
+    #[serde(default)]
+    wait: bool,
+    until: Option<String>,
+    idle_timeout: Option<u64>,
+    lines: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct AppStatus {
+    name: String,
+    status: &'static str,
+    crescent_pid: Option<u32>,
+    subprocess_pid: Option<u32>,
+    health: Option<HealthStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failed: Option<FailedState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    application: Option<Application>,
+}
+
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl ToString) -> Self {
+        ApiError {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        let status = match err {
+            Error::AppNotFound
+            | Error::ProfileNotFound
+            | Error::StackNotFound
+            | Error::LogNotFound => 404,
+            Error::AppNotRunning | Error::AlreadyRunning => 409,
+            _ => 500,
+        };
+
+        ApiError::new(status, err)
+    }
+}
+
+// The commands return an `Error` for the failures with their own status.
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<Error>() {
+            Ok(err) => err.into(),
+            Err(err) => ApiError::new(500, err),
+        }
+    }
+}
+
+impl ApiArgs {
+    pub fn run(self) -> Result<()> {
+        let token = match &self.auth_token_file {
+            Some(path) => Some(Arc::new(remote::read_token(path)?)),
+            None => None,
+        };
+
+        let server = match &self.socket {
+            Some(path) => {
+                remove_stale_socket(path)?;
+
+                let server = Server::http_unix(path)
+                    .map_err(|err| anyhow!("Error listening on '{}': {err}.", path.display()))?;
+
+                // Other users would manage the applications as this user, skipping their access lists.
+                fs::set_permissions(path, Permissions::from_mode(0o600)).context(format!(
+                    "Error setting permissions of '{}'.",
+                    path.display()
+                ))?;
+
+                println!("Listening on '{}'.", path.display());
+                server
+            }
+            None => {
+                check_loopback(&self.listen)?;
+
+                // Every local user can connect to a loopback address.
+                if token.is_none() {
+                    return Err(anyhow!(
+                        "Listening on an address needs '--auth-token-file', or use '--socket'."
+                    ));
+                }
+
+                let server = Server::http(&self.listen)
+                    .map_err(|err| anyhow!("Error listening on '{}': {err}.", self.listen))?;
+
+                println!("Listening on 'http://{}'.", self.listen);
+                server
+            }
+        };
+
+        // Output streams stay open while the application runs, every request gets its own thread.
+        for request in server.incoming_requests() {
+            let token = token.clone();
+
+            thread::Builder::new()
+                .name(String::from("api_request"))
+                .spawn(move || handle_request(request, token.as_deref()))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check_loopback(listen: &str) -> Result<()> {
+    let mut addresses = listen
+        .to_socket_addrs()
+        .context(format!("Invalid listen address '{listen}'."))?;
+
+    if !addresses.all(|address| address.ip().is_loopback()) {
+        return Err(anyhow!(
+            "The API can only listen on a loopback address, like '127.0.0.1:7600'."
+        ));
+    }
+
+    Ok(())
+}
+
+// A socket left behind by a server that didn't shut down is removed, one still in use is not.
+fn remove_stale_socket(path: &PathBuf) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    if UnixStream::connect(path).is_ok() {
+        return Err(anyhow!(
+            "An API server is already listening on '{}'.",
+            path.display()
+        ));
+    }
+
+    std::fs::remove_file(path).context(format!("Error removing '{}'.", path.display()))
+}
+
+fn route(method: &Method, url: &str) -> Route {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (method, segments.as_slice()) {
+        (Method::Get, ["apps"]) => Route::List,
+        (Method::Post, ["apps"]) => Route::Start,
+        (Method::Get, ["apps", name]) => Route::Status(name.to_string()),
+        (Method::Post, ["apps", name, "stop"]) => Route::Stop(name.to_string()),
+        (Method::Post, ["apps", name, "kill"]) => Route::Kill(name.to_string()),
+        (Method::Post, ["apps", name, "signal"]) => Route::Signal(name.to_string()),
+        (Method::Post, ["apps", name, "send"]) => Route::Send(name.to_string()),
+        (Method::Get, ["apps", name, "log"]) => {
+            let lines = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("lines="))
+                .and_then(|lines| lines.parse().ok())
+                .unwrap_or(DEFAULT_LOG_LINES);
+
+            Route::Log(name.to_string(), lines)
+        }
+        (Method::Get, ["apps", name, "output"]) => Route::Output(name.to_string()),
+        _ => Route::NotFound,
+    }
+}
+
+fn handle_request(mut request: Request, token: Option<&String>) {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+
+    if let Err(err) = check_request(&request, token) {
+        respond(request, &method, &url, Err(err));
+        return;
+    }
+
+    let result = match route(&method, &url) {
+        Route::List => list(),
+        Route::Start => read_body(&mut request).and_then(start),
+        Route::Status(name) => status(&name),
+        Route::Stop(name) => read_body(&mut request).and_then(|body| stop(&name, body)),
+        Route::Kill(name) => signal(&name, 9),
+        Route::Signal(name) => {
+            read_body(&mut request).and_then(|body: SignalRequest| signal(&name, body.signal))
+        }
+        Route::Send(name) => read_body(&mut request).and_then(|body| send_command(&name, body)),
+        Route::Log(name, lines) => log_lines(&name, lines),
+        Route::Output(name) => match application::check_app_exists(&name)
+            .and_then(|_| application::app_already_running(&name))
+        {
+            Ok(true) => {
+                println!("{method} {url} 200");
+                stream_output(request, &name);
+                return;
+            }
+            Ok(false) => Err(ApiError::new(409, "Application not running.")),
+            Err(err) => Err(err.into()),
+        },
+        Route::NotFound => Err(ApiError::new(404, "Not found.")),
+    };
+
+    respond(request, &method, &url, result);
+}
+
+fn respond(request: Request, method: &Method, url: &str, result: Result<Value, ApiError>) {
+    let (status, body) = match result {
+        Ok(body) => (200, body),
+        Err(err) => (err.status, json!({ "error": err.message })),
+    };
+
+    println!("{method} {url} {status}");
+
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header);
+
+    if let Err(err) = request.respond(response) {
+        eprintln!("Error responding to '{method} {url}': {err}.");
+    }
+}
+
+// Browsers can send requests to loopback addresses from any page, they are told apart by the
+// Host header, which is the name of the page for DNS rebinding, and the Content-Type, which
+// is never 'application/json' for forms.
+fn check_request(request: &Request, token: Option<&String>) -> Result<(), ApiError> {
+    let header = |name: &'static str| {
+        request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv(name))
+            .map(|header| header.value.as_str())
+    };
+
+    if !header("Host").is_some_and(is_loopback_host) {
+        return Err(ApiError::new(403, "Host has to be a loopback address."));
+    }
+
+    if let Some(token) = token {
+        let authorized = header("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|received| remote::tokens_match(received.trim(), token));
+
+        if !authorized {
+            return Err(ApiError::new(401, "Authentication failed."));
+        }
+    }
+
+    if *request.method() == Method::Post && !header("Content-Type").is_some_and(is_json) {
+        return Err(ApiError::new(
+            415,
+            "Content-Type has to be 'application/json'.",
+        ));
+    }
+
+    Ok(())
+}
+
+// 'localhost' or a loopback IP address, with or without a port.
+fn is_loopback_host(host: &str) -> bool {
+    let host = match host.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((address, _)) => address,
+            None => return false,
+        },
+        None => host.split(':').next().unwrap_or(host),
+    };
+
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+fn is_json(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+}
+
+// An empty body is read as an empty object, requests with only optional fields can skip it.
+fn read_body<T: DeserializeOwned>(request: &mut Request) -> Result<T, ApiError> {
+    let mut body = String::new();
+
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(|err| ApiError::new(400, format!("Error reading body: {err}.")))?;
+
+    if body.trim().is_empty() {
+        body = String::from("{}");
+    }
+
+    serde_json::from_str(&body).map_err(|err| ApiError::new(400, format!("Invalid body: {err}.")))
+}
+
+fn list() -> Result<Value, ApiError> {
+    let mut apps = vec![];
+
+    for name in crescent::get_app_names()? {
+        apps.push(app_status(&name, false)?);
+    }
+
+    Ok(json!(apps))
+}
+
+fn status(name: &String) -> Result<Value, ApiError> {
+    application::check_app_exists(name)?;
+    Ok(json!(app_status(name, true)?))
+}
+
+fn app_status(name: &String, details: bool) -> Result<AppStatus> {
+    let mut status = AppStatus {
+        name: name.clone(),
+        status: "stopped",
+        crescent_pid: None,
+        subprocess_pid: None,
+        health: None,
+        failed: None,
+        application: None,
+    };
+
+    if !application::app_already_running(name)? {
+        if let Some(failed) = application::get_app_failed_state(name)? {
+            status.status = "failed";
+
+            if details {
+                status.failed = Some(failed);
+            }
+        }
+
+        return Ok(status);
+    }
+
+    let pids = application::app_pids_by_name(name)?;
+
+    status.status = "running";
+    status.crescent_pid = pids.first().map(|pid| pid.as_u32());
+    status.subprocess_pid = pids.get(1).map(|pid| pid.as_u32());
+    status.health = application::get_app_health(name).unwrap_or_default();
+
+    if details {
+        // Secrets are masked, like for clients of the supervisor that are not admins.
+        let mut app_info = application::get_app_info(name)?;
+        environment::mask_application(&mut app_info);
+        status.application = Some(app_info);
+    }
+
+    Ok(status)
+}
+
+// `cres start` daemonizes, it runs as its own process and is waited on until the application is ready.
+fn start(body: StartRequest) -> Result<Value, ApiError> {
+    let start_args = StartArgs {
+        profile: Some(body.profile.clone()),
+        name: body.name,
+        ..Default::default()
+    };
+
+    let name = start_args
+        .into_application()
+        .map_err(|err| ApiError::new(400, err))?
+        .name;
+
+    if application::app_already_running(&name)? {
+        return Err(Error::AlreadyRunning.into());
+    }
+
+    let output = util::get_base_command(util::get_exec_path())
+        .args([
+            "start",
+            "--profile",
+            &body.profile,
+            "--name",
+            &name,
+            "--wait",
+        ])
+        .output()
+        .context("Error running 'cres start'.")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        let err = stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("Error starting the application.");
+
+        return Err(ApiError::new(500, err.trim_start_matches("Error: ")));
+    }
+
+    Ok(json!({ "name": name }))
+}
+
+fn stop(name: &String, body: StopRequest) -> Result<Value, ApiError> {
+    signals::check_stoppable(name)?;
+
+    let step = application::stop_app(name, body.force, body.timeout)?;
+    Ok(json!({ "result": step.to_string() }))
+}
+
+fn signal(name: &String, signal: u8) -> Result<Value, ApiError> {
+    signals::signal_app(name, signal)?;
+    Ok(json!({ "signal": signal }))
+}
+
+fn send_command(name: &String, body: SendRequest) -> Result<Value, ApiError> {
+    if body.command.trim().is_empty() {
+        return Err(ApiError::new(400, "Command empty."));
+    }
+
+    if !body.wait {
+        send::write_command(name, &body.command)?;
+        return Ok(json!({}));
+    }
+
+    let capture = Capture {
+        until: body.until,
+        idle_timeout: body.idle_timeout.unwrap_or(send::DEFAULT_IDLE_TIMEOUT),
+        max_lines: body.lines,
+    };
+
+    capture.validate().map_err(|err| ApiError::new(400, err))?;
+
+    let (lines, matched) = send::capture_output(name, &body.command, &capture)?;
+    Ok(json!({ "lines": lines, "matched": matched }))
+}
+
+fn log_lines(name: &String, lines: usize) -> Result<Value, ApiError> {
+    let mut log = tail::Tail::new(log::log_path(name)?)?;
+
+    let lines = match log.length {
+        0 => vec![],
+        _ => log.read_lines(lines)?,
+    };
+
+    Ok(json!({ "lines": lines }))
+}
+
+// Server-Sent Events, every line of output is a `data` event until the application stops.
+fn stream_output(request: Request, name: &String) {
+    let mut writer = request.into_writer();
+
+    let result = writer
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        )
+        .and_then(|_| writer.flush())
+        .map_err(anyhow::Error::from)
+        .and_then(|_| {
+            // Stops following once the client disconnects.
+            application::follow_output(name, |line| {
+                write!(writer, "data: {line}\n\n")
+                    .and_then(|_| writer.flush())
+                    .is_ok()
+            })
+            .map_err(anyhow::Error::from)
+        });
+
+    let _ = match result {
+        Ok(_) => write!(writer, "event: end\ndata: Application stopped.\n\n"),
+        Err(err) => write!(writer, "event: error\ndata: {err}\n\n"),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_api_route() {
+        assert_eq!(route(&Method::Get, "/apps"), Route::List);
+        assert_eq!(route(&Method::Post, "/apps/"), Route::Start);
+        assert_eq!(
+            route(&Method::Get, "/apps/lobby"),
+            Route::Status(String::from("lobby"))
+        );
+        assert_eq!(
+            route(&Method::Post, "/apps/lobby/stop"),
+            Route::Stop(String::from("lobby"))
+        );
+        assert_eq!(
+            route(&Method::Get, "/apps/lobby/log"),
+            Route::Log(String::from("lobby"), DEFAULT_LOG_LINES)
+        );
+        assert_eq!(
+            route(&Method::Get, "/apps/lobby/log?follow=false&lines=20"),
+            Route::Log(String::from("lobby"), 20)
+        );
+        assert_eq!(
+            route(&Method::Get, "/apps/lobby/output"),
+            Route::Output(String::from("lobby"))
+        );
+        assert_eq!(route(&Method::Get, "/apps/lobby/stop"), Route::NotFound);
+        assert_eq!(route(&Method::Delete, "/apps/lobby"), Route::NotFound);
+        assert_eq!(route(&Method::Get, "/"), Route::NotFound);
+    }
+
+    #[test]
+    fn unit_api_check_loopback() {
+        assert!(check_loopback("127.0.0.1:7600").is_ok());
+        assert!(check_loopback("[::1]:7600").is_ok());
+
+        let err = check_loopback("0.0.0.0:7600").unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "The API can only listen on a loopback address, like '127.0.0.1:7600'."
+        );
+
+        let args = ApiArgs {
+            listen: String::from("127.0.0.1:7600"),
+            socket: None,
+            auth_token_file: None,
+        };
+        let err = args.run().unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Listening on an address needs '--auth-token-file', or use '--socket'."
+        );
+    }
+
+    #[test]
+    fn unit_api_check_headers() {
+        assert!(is_loopback_host("localhost"));
+        assert!(is_loopback_host("127.0.0.1:7600"));
+        assert!(is_loopback_host("[::1]:7600"));
+        assert!(!is_loopback_host("attacker.example:7600"));
+        assert!(!is_loopback_host("192.168.1.10"));
+        assert!(!is_loopback_host("[::1"));
+
+        assert!(is_json("application/json"));
+        assert!(is_json("application/json; charset=utf-8"));
+        assert!(!is_json("text/plain"));
+        assert!(!is_json("application/x-www-form-urlencoded"));
+    }
+
+    #[test]
+    fn unit_api_error_status() {
+        let status = |err: anyhow::Error| ApiError::from(err).status;
+
+        assert_eq!(status(Error::LogNotFound.into()), 404);
+        assert_eq!(status(Error::AlreadyRunning.into()), 409);
+        assert_eq!(status(anyhow!("Log file does not exist.")), 500);
+
+        let err = ApiError::from(anyhow::Error::from(Error::AlreadyRunning));
+        assert_eq!(
+            err.message,
+            "An application with the same name is already running."
+        );
+    }
+}
@@ -1,20 +1,15 @@
-use std::{
-    io::{self, Write},
-    os::unix::net::UnixStream,
-    path::PathBuf,
-    sync::Mutex,
-    thread,
-    time::Duration,
-    vec,
-};
+use std::{io, path::PathBuf, sync::Mutex, thread, time::Duration, vec};
 
 use crate::{
     application,
-    subprocess::{self, SocketEvent},
+    error::Error,
+    remote,
+    socket::{self, Message, Stream},
+    subprocess::SocketEvent,
     tail,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use clap::Args;
 use crossbeam::channel::{tick, unbounded, Receiver, Sender};
 use crossterm::{
@@ -22,7 +17,7 @@ use crossterm::{
     execute,
     terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use log::{debug, LevelFilter};
+use log::{debug, error, LevelFilter};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout},
@@ -74,7 +69,7 @@ impl AttachArgs {
         application::check_app_exists(&self.name)?;
 
         if !application::app_already_running(&self.name)? {
-            return Err(anyhow!("Application not running."));
+            return Err(Error::AppNotRunning.into());
         }
 
         if !cfg!(test) {
@@ -82,24 +77,31 @@ impl AttachArgs {
             set_default_level(LevelFilter::Debug);
         }
 
-        let pids = application::app_pids_by_name(&self.name)?;
-
         let (sender, receiver) = unbounded();
         let (socket_sender, socket_receiver): (Sender<SocketEvent>, Receiver<SocketEvent>) =
             unbounded();
-        let (log_sender, log_receiver): (Sender<String>, Receiver<String>) = unbounded();
 
-        let app_dir = application::app_dir_by_name(&self.name)?;
-        let log_dir = app_dir.join(self.name.clone() + ".log");
-        let socket_dir = app_dir.join(self.name.clone() + ".sock");
-
-        let mut app = AttachTerminal::new(self.name);
+        let mut app = AttachTerminal::new(self.name.clone());
         let mut stats_list = String::from("Waiting for stats.");
 
+        let stream = application::connect_app(&self.name)?;
+
         event_read_handler(sender.clone());
-        log_handler(log_dir, sender.clone(), log_sender, log_receiver)?;
-        stats_handler(pids[1], sender.clone());
-        socket_handler(socket_dir, sender, socket_receiver)?;
+
+        // Only the output and commands are available for applications on other hosts.
+        if remote::get().is_some() {
+            stats_list = String::from("Stats are not available for remote applications.");
+            log_handler(self.name, None, sender.clone())?;
+        } else {
+            let pids = application::app_pids_by_name(&self.name)?;
+            let app_dir = application::app_dir_by_name(&self.name)?;
+            let log_dir = app_dir.join(self.name.clone() + ".log");
+
+            log_handler(self.name, Some(log_dir), sender.clone())?;
+            stats_handler(pids[1], sender.clone());
+        }
+
+        socket_handler(stream, sender, socket_receiver)?;
 
         socket_sender.send(SocketEvent::CommandHistory(vec![]))?;
 
@@ -212,11 +214,14 @@ impl AttachArgs {
                     stats_list = stats;
                     terminal.draw(|f| ui(f, &mut app, &stats_list))?;
                 }
-                TerminalEvent::SocketEvent(message) => {
-                    if let SocketEvent::CommandHistory(history) = message {
-                        app.history = history
+                TerminalEvent::SocketEvent(message) => match message {
+                    SocketEvent::CommandHistory(history) => app.history = history,
+                    SocketEvent::Error(err) => {
+                        error!("{err}");
+                        terminal.draw(|f| ui(f, &mut app, &stats_list))?;
                     }
-                }
+                    _ => {}
+                },
             }
         }
 
@@ -245,55 +250,37 @@ fn event_read_handler(input: Sender<TerminalEvent>) {
 }
 
 fn socket_handler(
-    socket_dir: PathBuf,
+    mut stream: Stream,
     sender: Sender<TerminalEvent>,
     socket_receiver: Receiver<SocketEvent>,
 ) -> Result<(), anyhow::Error> {
-    if !socket_dir.exists() {
-        return Err(anyhow!("Socket file does not exist."));
-    }
-
-    let mut stream = UnixStream::connect(socket_dir)?;
     let mut s = stream.try_clone()?;
 
     thread::spawn(move || {
         for received in socket_receiver {
-            let json = match received {
-                SocketEvent::CommandHistory(_content) => {
-                    Some(serde_json::to_vec(&SocketEvent::CommandHistory(_content)))
-                }
-                SocketEvent::WriteStdin(command) => {
-                    Some(serde_json::to_vec(&SocketEvent::WriteStdin(command)))
-                }
-                _ => None,
+            let event = match received {
+                SocketEvent::CommandHistory(_) | SocketEvent::WriteStdin(_) => received,
+                _ => continue,
             };
 
-            if let Some(event) = json {
-                match event {
-                    Ok(event) => {
-                        s.write_all(&event).unwrap();
-                    }
-                    Err(err) => {
-                        debug!("Error serializing event: {err}")
-                    }
-                }
+            if let Err(err) = socket::write_event(&mut s, &Message::new(event)) {
+                debug!("Error writing event: {err}")
             }
         }
     });
 
-    let mut received = vec![0u8; 1024];
-    let mut read: usize = 0;
-
-    thread::spawn(move || {
-        while subprocess::read_socket_stream(&mut stream, &mut received, &mut read) > 0 {
-            match serde_json::from_slice::<SocketEvent>(&received[..read]) {
-                Ok(message) => {
-                    sender.send(TerminalEvent::SocketEvent(message)).unwrap();
-                }
-                Err(err) => {
-                    debug!("Error converting socket message to struct: {err}")
-                }
-            };
+    thread::spawn(move || loop {
+        match socket::read_event::<_, Message>(&mut stream) {
+            Ok(Some(message)) => {
+                sender
+                    .send(TerminalEvent::SocketEvent(message.event))
+                    .unwrap();
+            }
+            Ok(None) => break,
+            Err(err) => {
+                debug!("Error reading socket event: {err}");
+                break;
+            }
         }
     });
 
@@ -301,22 +288,26 @@ fn socket_handler(
 }
 
 fn log_handler(
-    log_dir: PathBuf,
+    name: String,
+    log_dir: Option<PathBuf>,
     sender: Sender<TerminalEvent>,
-    log_sender: Sender<String>,
-    log_receiver: Receiver<String>,
 ) -> Result<(), anyhow::Error> {
-    let mut log = tail::Tail::new(log_dir)?;
+    if let Some(log_dir) = log_dir {
+        let mut log = tail::Tail::new(log_dir)?;
 
-    let lines = log.read_lines(200)?;
+        let lines = log.read_lines(200)?;
 
-    sender.send(TerminalEvent::Log(lines))?;
-
-    thread::spawn(move || log.watch(&log_sender));
+        sender.send(TerminalEvent::Log(lines))?;
+    }
 
     thread::spawn(move || {
-        for line in log_receiver {
-            sender.send(TerminalEvent::Log([line].to_vec())).unwrap();
+        // Stops following once the UI is closed.
+        let result = application::follow_output(&name, |line| {
+            sender.send(TerminalEvent::Log(vec![line])).is_ok()
+        });
+
+        if let Err(err) = result {
+            error!("{err}");
         }
     });
 
@@ -428,7 +419,7 @@ mod tests {
         env::temp_dir,
         fs::{remove_file, File},
         io::Write,
-        os::unix::net::UnixListener,
+        os::unix::net::{UnixListener, UnixStream},
     };
 
     #[test]
@@ -467,19 +458,25 @@ mod tests {
         let (sender, receiver) = unbounded();
         let (socket_sender, socket_receiver): (Sender<SocketEvent>, Receiver<SocketEvent>) =
             unbounded();
-        let (log_sender, log_receiver): (Sender<String>, Receiver<String>) = unbounded();
         let pid = Pid::from(std::process::id() as usize);
 
         event_read_handler(sender.clone());
-        log_handler(log_dir, sender.clone(), log_sender.clone(), log_receiver)?;
+        log_handler(
+            String::from("attach_handlers"),
+            Some(log_dir),
+            sender.clone(),
+        )?;
         stats_handler(pid, sender.clone());
-        socket_handler(socket_dir, sender, socket_receiver)?;
+        socket_handler(
+            Stream::Unix(UnixStream::connect(&socket_dir)?),
+            sender,
+            socket_receiver,
+        )?;
 
         if let TerminalEvent::Stats(a) = receiver.recv_timeout(Duration::from_secs(3))? {
             assert!(a.contains("load"));
         }
 
-        log_sender.send("log".to_string())?;
         socket_sender.send(SocketEvent::WriteStdin("".to_string()))?;
 
         Ok(())
@@ -0,0 +1,136 @@
+use std::{
+    fs::{self, File},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    crescent,
+    daemon::{self, DaemonEvent},
+    logger, util,
+};
+
+use anyhow::{anyhow, Result};
+use clap::{Args, Subcommand};
+use daemonize::{Daemonize, Outcome};
+use log::LevelFilter;
+
+#[derive(Args)]
+#[command(about = "Supervise every application from a single long-lived process.")]
+pub struct DaemonArgs {
+    #[command(subcommand)]
+    pub action: DaemonAction,
+}
+
+#[derive(Subcommand)]
+pub enum DaemonAction {
+    #[command(
+        about = "Start the daemon, applications started while it runs are supervised by it."
+    )]
+    Start {
+        #[arg(
+            short,
+            long,
+            help = "Run in the foreground instead of daemonizing, for service managers."
+        )]
+        foreground: bool,
+    },
+    #[command(about = "Stop every application supervised by the daemon and the daemon itself.")]
+    Stop,
+    #[command(about = "Print the daemon PID and the applications it supervises.")]
+    Status,
+}
+
+static LOGGER: logger::Logger = logger::Logger;
+
+// Time for a daemonized daemon to start listening.
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl DaemonArgs {
+    pub fn run(self) -> Result<()> {
+        match self.action {
+            DaemonAction::Start { foreground } => start(foreground),
+            DaemonAction::Stop => stop(),
+            DaemonAction::Status => status(),
+        }
+    }
+}
+
+fn start(foreground: bool) -> Result<()> {
+    if daemon::request(DaemonEvent::List)?.is_some() {
+        return Err(anyhow!("The daemon is already running."));
+    }
+
+    if !foreground {
+        let daemonize = Daemonize::new()
+            .pid_file(daemon::daemon_path("pid")?)
+            .working_directory(crescent::crescent_dir()?)
+            .stderr(File::create(daemon::daemon_path("log")?)?);
+
+        match daemonize.execute() {
+            Outcome::Parent(result) => {
+                result?;
+                return wait_daemon_started();
+            }
+            Outcome::Child(result) => {
+                result?;
+            }
+        }
+    } else {
+        fs::write(daemon::daemon_path("pid")?, std::process::id().to_string())?;
+    }
+
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Info);
+
+    daemon::run()
+}
+
+fn wait_daemon_started() -> Result<()> {
+    let start = Instant::now();
+
+    while start.elapsed() < START_TIMEOUT {
+        if daemon::request(DaemonEvent::List)?.is_some() {
+            println!("Daemon started.");
+            return Ok(());
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    Err(anyhow!(
+        "Daemon did not start, check '{}'.",
+        daemon::daemon_path("log")?.display()
+    ))
+}
+
+fn stop() -> Result<()> {
+    match daemon::request(DaemonEvent::Shutdown)? {
+        Some(_) => {
+            println!("Daemon stopped.");
+            Ok(())
+        }
+        None => Err(anyhow!("The daemon is not running.")),
+    }
+}
+
+fn status() -> Result<()> {
+    let apps = match daemon::request(DaemonEvent::List)? {
+        Some(DaemonEvent::Apps(apps)) => apps,
+        Some(_) => return Err(anyhow!("Unexpected response from the daemon.")),
+        None => return Err(anyhow!("The daemon is not running.")),
+    };
+
+    let pid = fs::read_to_string(daemon::daemon_path("pid")?)?;
+
+    util::print_title_cyan("Daemon information");
+    util::println_field_white("PID", pid.trim());
+    util::println_field_white("Socket", daemon::daemon_path("sock")?.display());
+
+    match apps.is_empty() {
+        true => util::println_field_white("Applications", "None"),
+        false => util::println_field_white("Applications", apps.join(", ")),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use crate::{application, history, util};
+
+use anyhow::Result;
+use clap::Args;
+use tabled::{settings::Style, Table, Tabled};
+
+#[derive(Args)]
+#[command(about = "List past runs of an application.")]
+pub struct HistoryArgs {
+    #[arg(help = "Application name.")]
+    pub name: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Runs to print. Defaults to 20.",
+        default_value_t = 20
+    )]
+    pub limit: usize,
+}
+
+#[derive(Tabled)]
+struct RunInfo {
+    #[tabled(rename = "Started at")]
+    started_at: String,
+    #[tabled(rename = "PID")]
+    pid: u32,
+    #[tabled(rename = "Exited at")]
+    exited_at: String,
+    #[tabled(rename = "Exit")]
+    exit: String,
+    #[tabled(rename = "Runtime")]
+    runtime: String,
+    #[tabled(rename = "Stopped by")]
+    stopped_by: String,
+}
+
+impl HistoryArgs {
+    pub fn run(self) -> Result<()> {
+        application::check_app_exists(&self.name)?;
+
+        let runs = history::get_app_history(&self.name)?;
+
+        if runs.is_empty() {
+            println!("No history for '{}'.", self.name);
+            return Ok(());
+        }
+
+        let skip = runs.len().saturating_sub(self.limit);
+
+        let runs: Vec<RunInfo> = runs
+            .into_iter()
+            .skip(skip)
+            .map(|run| match run.exit {
+                Some(exit) => RunInfo {
+                    started_at: util::format_timestamp(run.started_at),
+                    pid: run.pid,
+                    exited_at: util::format_timestamp(exit.timestamp),
+                    exit: exit.status(),
+                    runtime: util::get_uptime_from_seconds(exit.runtime),
+                    stopped_by: match exit.stopped_by {
+                        Some(stopped_by) => stopped_by.to_string(),
+                        None => String::from("N/A"),
+                    },
+                },
+                None => RunInfo {
+                    started_at: util::format_timestamp(run.started_at),
+                    pid: run.pid,
+                    exited_at: String::from("Running."),
+                    exit: String::from("N/A"),
+                    runtime: String::from("N/A"),
+                    stopped_by: String::from("N/A"),
+                },
+            })
+            .collect();
+
+        let mut table = Table::new(runs);
+        table.with(Style::modern());
+        println!("{table}");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_history_run() -> Result<()> {
+        let name = "unit_history_run".to_string();
+        let command = HistoryArgs { name, limit: 20 };
+
+        let err = command.run().unwrap_err();
+        assert_eq!(format!("{}", err), "Application does not exist.");
+        Ok(())
+    }
+}
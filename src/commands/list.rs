@@ -1,10 +1,10 @@
-use std::{fs::ReadDir, iter::Flatten, vec};
+use std::vec;
 
-use crate::{application, crescent, util};
+use crate::{application, crescent, remote, util};
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use clap::Args;
-use sysinfo::{Pid, System};
+use sysinfo::System;
 use tabled::{settings::Style, Table, Tabled};
 
 #[derive(Args)]
@@ -15,8 +15,12 @@ pub struct ListArgs;
 struct ApplicationInfo {
     #[tabled(rename = "Name")]
     name: String,
+    #[tabled(rename = "Status")]
+    status: String,
+    #[tabled(rename = "Health")]
+    health: String,
     #[tabled(rename = "crescent PID")]
-    crescent_pid: Pid,
+    crescent_pid: String,
     #[tabled(rename = "Subprocess PID")]
     subprocess_pid: String,
     #[tabled(rename = "CWD")]
@@ -27,14 +31,10 @@ struct ApplicationInfo {
 
 impl ListArgs {
     pub fn run(self) -> Result<()> {
-        let apps_dir = crescent::get_apps_dir()?;
-
-        let dirs = apps_dir
-            .read_dir()
-            .context("Error reading apps directory.")?
-            .flatten();
-
-        let apps = self.get_applications_info(dirs)?;
+        let apps = match remote::get() {
+            Some(_) => self.get_remote_application_info()?,
+            None => self.get_applications_info(crescent::get_app_names()?)?,
+        };
 
         if apps.is_empty() {
             println!("No application running.");
@@ -52,23 +52,47 @@ impl ListArgs {
         Ok(table)
     }
 
-    fn get_applications_info(
-        &self,
-        crescent_dir: Flatten<ReadDir>,
-    ) -> Result<Vec<ApplicationInfo>> {
+    // The remote listener serves a single application, without access to its processes.
+    fn get_remote_application_info(&self) -> Result<Vec<ApplicationInfo>> {
+        let name = application::remote_app_name()?;
+
+        let health = match application::get_app_health(&name) {
+            Ok(Some(health)) => health.to_string(),
+            _ => String::from("N/A"),
+        };
+
+        Ok(vec![ApplicationInfo {
+            name,
+            status: String::from("Running"),
+            health,
+            crescent_pid: String::from("N/A"),
+            subprocess_pid: String::from("N/A"),
+            cwd: String::from("N/A"),
+            uptime: String::from("N/A"),
+        }])
+    }
+
+    fn get_applications_info(&self, names: Vec<String>) -> Result<Vec<ApplicationInfo>> {
         let mut system = System::new();
         system.refresh_processes();
 
         let mut apps = vec![];
 
-        for app_dir in crescent_dir {
-            let app_name = app_dir
-                .file_name()
-                .to_str()
-                .context("Error converting OsStr to str.")?
-                .to_string();
-
+        for app_name in names {
             if !application::app_already_running(&app_name)? {
+                // Failed applications are kept in the list until started again.
+                if application::get_app_failed_state(&app_name)?.is_some() {
+                    apps.push(ApplicationInfo {
+                        name: app_name,
+                        status: String::from("Failed"),
+                        health: String::from("N/A"),
+                        crescent_pid: String::from("N/A"),
+                        subprocess_pid: String::from("Not running."),
+                        cwd: String::from("N/A"),
+                        uptime: String::from("N/A"),
+                    });
+                }
+
                 continue;
             }
 
@@ -90,9 +114,16 @@ impl ListArgs {
                     None => String::from("N/A"),
                 };
 
+                let health = match application::get_app_health(&app_name) {
+                    Ok(Some(health)) => health.to_string(),
+                    _ => String::from("N/A"),
+                };
+
                 let app = ApplicationInfo {
                     name: app_name,
-                    crescent_pid: pids[0],
+                    status: String::from("Running"),
+                    health,
+                    crescent_pid: pids[0].to_string(),
                     subprocess_pid,
                     cwd,
                     uptime: util::get_uptime_from_seconds(process.run_time()),
@@ -110,7 +141,6 @@ impl ListArgs {
 mod tests {
     use super::*;
     extern crate test_utils;
-    use anyhow::Context;
     use serial_test::serial;
     use std::assert_eq;
 
@@ -121,23 +151,18 @@ mod tests {
         test_utils::start_long_running_service(name)?;
         assert!(test_utils::check_app_is_running(name)?);
 
-        let apps_dir = crescent::get_apps_dir()?;
-
-        let dirs = apps_dir
-            .read_dir()
-            .context("Error reading crescent directory.")?
-            .flatten();
+        let names = crescent::get_app_names()?;
 
         let list_command = ListArgs {};
 
-        let apps = list_command.get_applications_info(dirs)?;
+        let apps = list_command.get_applications_info(names)?;
         let app = apps.into_iter().find(|app| app.name == name).unwrap();
 
         assert_eq!(&app.name, &name);
 
         let table = list_command.create_table(vec![app])?;
         assert!(!table.is_empty());
-        assert_eq!(table.shape(), (2, 5));
+        assert_eq!(table.shape(), (2, 7));
 
         test_utils::shutdown_long_running_service(name)?;
         test_utils::delete_app_folder(name)?;
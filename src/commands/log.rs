@@ -1,10 +1,9 @@
-use std::{fs::OpenOptions, thread};
+use std::{fs::OpenOptions, path::PathBuf};
 
-use crate::{application, tail};
+use crate::{application, error::Error, tail};
 
 use anyhow::{anyhow, Result};
 use clap::Args;
-use crossbeam::channel::unbounded;
 
 #[derive(Args)]
 #[command(about = "Print, watch or flush the log file from an application.")]
@@ -29,15 +28,7 @@ pub struct LogArgs {
 
 impl LogArgs {
     pub fn run(self) -> Result<()> {
-        application::check_app_exists(&self.name)?;
-
-        let mut app_dir = application::app_dir_by_name(&self.name)?;
-
-        app_dir.push(format!("{}.log", self.name));
-
-        if !app_dir.is_file() {
-            return Err(anyhow!("Log file does not exist."));
-        }
+        let app_dir = log_path(&self.name)?;
 
         if self.flush {
             match OpenOptions::new().write(true).truncate(true).open(app_dir) {
@@ -73,20 +64,38 @@ impl LogArgs {
             return Ok(());
         }
 
-        println!(">> Watching log");
+        // New lines are sent by the supervisor.
+        if !application::app_already_running(&self.name)? {
+            return Err(Error::AppNotRunning.into());
+        }
 
-        let (sender, receiver) = unbounded();
+        println!(">> Watching log");
 
-        thread::spawn(move || log.watch(&sender));
+        application::follow_output(&self.name, |line| {
+            println!("{line}");
+            true
+        })?;
 
-        for content in receiver {
-            print!("{content}")
-        }
+        println!(">> Application stopped");
 
         Ok(())
     }
 }
 
+pub fn log_path(name: &String) -> Result<PathBuf> {
+    application::check_app_exists(name)?;
+
+    let mut app_dir = application::app_dir_by_name(name)?;
+
+    app_dir.push(format!("{name}.log"));
+
+    if !app_dir.is_file() {
+        return Err(Error::LogNotFound.into());
+    }
+
+    Ok(app_dir)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,9 +1,65 @@
+pub mod api;
 pub mod attach;
+pub mod daemon;
+pub mod history;
 pub mod list;
 pub mod log;
 pub mod profile;
+pub mod restart;
 pub mod save;
 pub mod send;
 pub mod signals;
+pub mod stack;
 pub mod start;
+pub mod startup;
 pub mod status;
+
+use crate::util;
+
+use anyhow::{anyhow, Result};
+use crossterm::style::Stylize;
+
+// Runs the action for every application, errors are reported at the end.
+// A single application keeps the output and error of the action as is.
+pub fn run_for_apps<F>(names: Vec<String>, mut action: F) -> Result<()>
+where
+    F: FnMut(&String) -> Result<()>,
+{
+    if names.is_empty() {
+        println!("No application running.");
+        return Ok(());
+    }
+
+    if names.len() == 1 {
+        return action(&names[0]);
+    }
+
+    let mut results = vec![];
+
+    for name in &names {
+        util::print_title_cyan(&format!("'{name}'"));
+        let result = action(name);
+        println!();
+        results.push((name, result));
+    }
+
+    util::print_title_cyan("Results");
+
+    let mut failed = 0;
+
+    for (name, result) in results {
+        match result {
+            Ok(_) => println!("{}: {}", name.clone().white(), "Ok".green()),
+            Err(err) => {
+                failed += 1;
+                println!("{}: {}", name.clone().white(), err.to_string().red())
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("{failed} of {} applications failed.", names.len()));
+    }
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::{crescent, util};
+use crate::{crescent, environment, util};
 
 use anyhow::Result;
 use clap::Args;
@@ -57,6 +57,93 @@ impl ProfileArgs {
             util::println_field_white("Stop command", stop_command);
         }
 
+        if let Some(stop_timeout) = profile.stop_timeout {
+            util::println_field_white("Stop timeout", stop_timeout);
+        }
+
+        if let Some(restart_policy) = profile.restart_policy {
+            util::println_field_white("Restart policy", format!("{restart_policy:?}"));
+        }
+
+        if let Some(restart_delay) = profile.restart_delay {
+            util::println_field_white("Restart delay", restart_delay);
+        }
+
+        if let Some(max_restarts) = profile.max_restarts {
+            util::println_field_white("Max restarts", max_restarts);
+        }
+
+        if let Some(crash_loop_limit) = profile.crash_loop_limit {
+            util::println_field_white("Crash loop limit", crash_loop_limit);
+        }
+
+        if let Some(crash_loop_window) = profile.crash_loop_window {
+            util::println_field_white("Crash loop window", crash_loop_window);
+        }
+
+        if let Some(health_check) = profile.health_check {
+            util::println_field_white("Health check", health_check);
+        }
+
+        if let Some(readiness) = profile.readiness {
+            util::println_field_white("Ready when", readiness);
+        }
+
+        if let Some(schedules) = profile.schedules {
+            for (expression, command) in schedules {
+                util::println_field_white(&format!("Schedule '{expression}'"), command.trim());
+            }
+        }
+
+        if let Some(scheduled_restart) = profile.scheduled_restart {
+            util::println_field_white("Scheduled restart", scheduled_restart.cron);
+
+            if let Some(warnings) = scheduled_restart.warnings {
+                util::println_field_white("Restart warnings", warnings.join(", "));
+            }
+
+            if let Some(warning_command) = scheduled_restart.warning_command {
+                util::println_field_white("Restart warning command", warning_command);
+            }
+        }
+
+        if let Some(remote) = profile.remote {
+            util::println_field_white("Remote listener", remote);
+        }
+
+        if let Some(access) = profile.access {
+            util::println_field_white("Access", access);
+        }
+
+        if let Some(depends_on) = profile.depends_on {
+            util::println_field_white("Depends on", depends_on.join(", "));
+        }
+
+        if let Some(labels) = profile.labels {
+            util::println_field_white("Labels", labels.join(", "));
+        }
+
+        if let Some(env_file) = profile.env_file {
+            util::println_field_white("Env file", env_file);
+        }
+
+        if let Some(env) = profile.env {
+            let env: Vec<String> = env
+                .iter()
+                .map(|(key, value)| format!("{key}={}", environment::mask(key, value)))
+                .collect();
+
+            util::println_field_white("Env", env.join(", "));
+        }
+
+        if let Some(clean_env) = profile.clean_env {
+            util::println_field_white("Clean env", clean_env);
+        }
+
+        if let Some(path) = profile.path {
+            util::println_field_white("PATH", path);
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use super::{run_for_apps, start};
+use crate::{
+    application::{self, Application},
+    crescent, dependencies,
+    error::Error,
+    history::StoppedBy,
+    socket,
+    subprocess::SocketEvent,
+    util,
+};
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+
+#[derive(Args)]
+#[command(about = "Stop an application and start it again with the same command line.")]
+pub struct RestartArgs {
+    #[arg(
+        help = "Application names, names separated by commas or glob patterns such as 'lobby-*'.",
+        required_unless_present_any = ["all", "labels"]
+    )]
+    pub names: Vec<String>,
+
+    #[arg(short, long, help = "Restart all running applications.")]
+    pub all: bool,
+
+    #[arg(
+        long = "label",
+        value_delimiter = ',',
+        help = "Select the running applications with this label, can be used multiple times."
+    )]
+    pub labels: Vec<String>,
+
+    #[arg(
+        short = 'p',
+        long = "profile",
+        help = "Name or path to a profile to reload fields from before restarting."
+    )]
+    pub profile: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "Seconds to wait before escalating to SIGTERM and then SIGKILL. Defaults to the profile 'stop_timeout' or 30."
+    )]
+    pub timeout: Option<u64>,
+}
+
+impl RestartArgs {
+    pub fn run(self) -> Result<()> {
+        let names = application::resolve_app_names(&self.names, self.all, &self.labels)?;
+
+        if names.len() == 1 {
+            return self.restart(&names[0]);
+        }
+
+        // Dependencies are restarted before the applications depending on them.
+        let names = dependencies::start_order(&dependencies::running_dependencies(names))?;
+
+        // Starting daemonizes the current process, each restart needs its own.
+        run_for_apps(names, |name| self.spawn_restart(name))
+    }
+
+    fn spawn_restart(&self, name: &String) -> Result<()> {
+        let exec_path = util::get_exec_path();
+        let mut cmd = util::get_base_command(exec_path);
+
+        cmd.arg("restart").arg(name);
+
+        if let Some(profile) = &self.profile {
+            cmd.arg("--profile").arg(profile);
+        }
+
+        if let Some(timeout) = self.timeout {
+            cmd.arg("--timeout").arg(timeout.to_string());
+        }
+
+        if !cmd.status()?.success() {
+            return Err(anyhow!("Error restarting '{name}'."));
+        }
+
+        Ok(())
+    }
+
+    fn restart(&self, name: &String) -> Result<()> {
+        application::check_app_exists(name)?;
+
+        if !application::app_already_running(name)? {
+            return Err(Error::AppNotRunning.into());
+        }
+
+        let mut app_info = application::get_app_info(name)?;
+
+        if let Some(profile) = self.profile.clone() {
+            app_info = reload_profile(app_info, profile)?;
+        }
+
+        application::require_capability(name, socket::CAPABILITY_STOP)?;
+
+        eprintln!("Waiting for '{name}' to stop.");
+
+        let event = SocketEvent::Stop {
+            force: false,
+            stopped_by: StoppedBy::Restart,
+            timeout: self.timeout,
+        };
+
+        match application::request_event(name, event)? {
+            SocketEvent::Stopped(step) => eprintln!("{step}"),
+            _ => return Err(anyhow!("Unexpected response from the application.")),
+        }
+
+        // The supervisor still has to remove its socket.
+        application::wait_app_exit(name, Duration::from_secs(10))?;
+
+        start::start(app_info, None)
+    }
+}
+
+// The profile is merged over the arguments of the first start, like when starting.
+fn reload_profile(mut app_info: Application, profile: String) -> Result<Application> {
+    let mut start_args = app_info
+        .cli_args
+        .take()
+        .unwrap_or_else(|| app_info.start_args.clone());
+
+    // Profiles for jars usually don't have a file path, reuse the current one.
+    if start_args.file_path.is_some() || crescent::get_profile(&profile)?.file_path.is_none() {
+        start_args.file_path = Some(app_info.file_path.to_string_lossy().to_string());
+    }
+
+    start_args.name = Some(app_info.name.clone());
+    start_args.profile = Some(profile);
+
+    // Resolved by `start --saved` or `stack up`, the variables are kept as they are.
+    let env_stdin = std::mem::take(&mut start_args.env_stdin);
+    let env = app_info.env.take();
+
+    let mut reloaded = start_args.into_application()?;
+
+    if env_stdin {
+        reloaded.env = env;
+    }
+
+    Ok(reloaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_restart_run() -> Result<()> {
+        let name = "unit_restart_run".to_string();
+        let command = RestartArgs {
+            names: vec![name],
+            all: false,
+            labels: vec![],
+            profile: None,
+            timeout: None,
+        };
+
+        let err = command.run().unwrap_err();
+        assert_eq!(format!("{}", err), "Application does not exist.");
+        Ok(())
+    }
+}
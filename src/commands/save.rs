@@ -1,12 +1,15 @@
-use std::fs::File;
+use std::{
+    fs::{self, OpenOptions, Permissions},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+};
 
 use crate::{
     application::{self, Application},
-    crescent,
+    crescent, environment,
     util::print_title_cyan,
 };
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use clap::Args;
 use crossterm::style::Stylize;
 use serde::{Deserialize, Serialize};
@@ -22,19 +25,14 @@ pub struct SaveArgs;
 
 impl SaveArgs {
     pub fn run(self) -> Result<()> {
-        let apps_dir = crescent::get_apps_dir()?;
-
-        let dirs = apps_dir
-            .read_dir()
-            .context("Error reading apps directory.")?
-            .flatten();
-
         let mut save = SaveFile { apps: vec![] };
 
-        for app_dir in dirs {
-            let name = app_dir.file_name().to_str().unwrap().to_string();
+        for name in crescent::get_app_names()? {
+            if let Ok(mut app_info) = application::get_app_info(&name) {
+                if let Some(env_file) = &app_info.start_args.env_file {
+                    app_info.env = environment::without_env_file(app_info.env, env_file)?;
+                }
 
-            if let Ok(app_info) = application::get_app_info(&name) {
                 save.apps.push(app_info);
             }
         }
@@ -47,7 +45,14 @@ impl SaveArgs {
         let mut save_dir = crescent::crescent_dir()?;
         save_dir.push("apps.json");
 
-        let save_file = File::create(save_dir)?;
+        // The environment of the applications can still contain secrets.
+        let save_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&save_dir)?;
+        fs::set_permissions(&save_dir, Permissions::from_mode(0o600))?;
         serde_json::to_writer_pretty(save_file, &save)?;
 
         print_title_cyan(&format!("Saved {} apps:", save.apps.len()));
@@ -1,41 +1,143 @@
-use std::{io::Write, os::unix::net::UnixStream};
+use crate::{
+    application, commands::run_for_apps, output::Capture, socket, subprocess::SocketEvent,
+};
 
-use crate::{application, subprocess::SocketEvent};
-
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, Result};
 use clap::Args;
 
 #[derive(Args)]
 #[command(about = "Send a command to an application.")]
 pub struct SendArgs {
-    #[arg(help = "Application name.")]
-    pub name: String,
+    #[arg(
+        help = "Application name, names separated by commas or a glob pattern such as 'lobby-*'.",
+        required_unless_present_any = ["all", "labels"]
+    )]
+    pub name: Option<String>,
 
     #[arg(help = "Command to send.", allow_hyphen_values = true)]
     pub command: Vec<String>,
+
+    #[arg(short, long, help = "Send the command to all running applications.")]
+    pub all: bool,
+
+    #[arg(
+        long = "label",
+        value_delimiter = ',',
+        help = "Select the running applications with this label, can be used multiple times."
+    )]
+    pub labels: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "Print the output that follows the command, stopping after the idle timeout."
+    )]
+    pub wait: bool,
+
+    #[arg(
+        long,
+        help = "Stop waiting after a line matching this regex, failing if none does.",
+        requires = "wait"
+    )]
+    pub until: Option<String>,
+
+    #[arg(
+        long,
+        help = "Stop waiting when no line is written for this many seconds. Defaults to 2.",
+        requires = "wait"
+    )]
+    pub idle_timeout: Option<u64>,
+
+    #[arg(long, help = "Stop waiting after this many lines.", requires = "wait")]
+    pub lines: Option<usize>,
 }
 
+pub const DEFAULT_IDLE_TIMEOUT: u64 = 2;
+
 impl SendArgs {
-    pub fn run(self) -> Result<()> {
-        application::check_app_exists(&self.name)?;
+    pub fn run(mut self) -> Result<()> {
+        let mut patterns = vec![];
+
+        // Without a name, the first word of the command is parsed as one.
+        match self.name.take() {
+            Some(name) if self.all || !self.labels.is_empty() => self.command.insert(0, name),
+            Some(name) => patterns.push(name),
+            None => {}
+        }
 
-        if self.command.join(" ").trim().is_empty() {
+        let command = self.command.join(" ");
+
+        if command.trim().is_empty() {
             return Err(anyhow!("Command empty."));
         }
 
-        let mut app_dir = application::app_dir_by_name(&self.name)?;
+        let capture = match self.wait {
+            true => {
+                let capture = Capture {
+                    until: self.until,
+                    idle_timeout: self.idle_timeout.unwrap_or(DEFAULT_IDLE_TIMEOUT),
+                    max_lines: self.lines,
+                };
 
-        app_dir.push(self.name.clone() + ".sock");
+                capture.validate()?;
+                Some(capture)
+            }
+            false => None,
+        };
 
-        let mut stream = UnixStream::connect(app_dir)
-            .context(format!("Error connecting to '{}' socket.", self.name))?;
+        let names = application::resolve_app_names(&patterns, self.all, &self.labels)?;
 
-        let event = serde_json::to_vec(&SocketEvent::WriteStdin(self.command.join(" ")))?;
+        run_for_apps(names, |name| match &capture {
+            Some(capture) => capture_command(name, &command, capture),
+            None => send_command(name, &command),
+        })
+    }
+}
 
-        stream.write_all(&event)?;
+fn capture_command(name: &String, command: &str, capture: &Capture) -> Result<()> {
+    let (lines, matched) = capture_output(name, command, capture)?;
+
+    for line in lines {
+        println!("{line}");
+    }
+
+    match &capture.until {
+        Some(until) if !matched => Err(anyhow!("No output matched '{until}'.")),
+        _ => Ok(()),
+    }
+}
+
+fn send_command(name: &String, command: &str) -> Result<()> {
+    write_command(name, command)?;
+    println!("Command sent.");
+    Ok(())
+}
+
+// Returns the lines that followed the command and if `until` matched.
+pub fn capture_output(
+    name: &String,
+    command: &str,
+    capture: &Capture,
+) -> Result<(Vec<String>, bool)> {
+    application::check_app_exists(name)?;
+    application::require_capability(name, socket::CAPABILITY_CAPTURE)?;
+
+    let event = SocketEvent::Capture {
+        command: command.to_string(),
+        capture: capture.clone(),
+    };
+
+    match application::request_event(name, event)? {
+        SocketEvent::Captured { lines, matched } => Ok((lines, matched)),
+        _ => Err(anyhow!("Unexpected response from the application.")),
+    }
+}
 
-        println!("Command sent.");
+pub fn write_command(name: &String, command: &str) -> Result<()> {
+    application::check_app_exists(name)?;
 
-        Ok(())
+    match application::request_event(name, SocketEvent::WriteStdin(command.to_string()))? {
+        SocketEvent::Ack => Ok(()),
+        _ => Err(anyhow!("Unexpected response from the application.")),
     }
 }
@@ -1,9 +1,4 @@
-use std::{io::Write, os::unix::net::UnixStream};
-
-use crate::{
-    application,
-    subprocess::{self, SocketEvent},
-};
+use crate::{application, commands::run_for_apps, dependencies, error::Error, socket, subprocess};
 
 use anyhow::{anyhow, Context, Result};
 use clap::Args;
@@ -11,84 +6,168 @@ use clap::Args;
 #[derive(Args)]
 #[command(about = "Send a signal to the application subprocess.")]
 pub struct SignalArgs {
-    #[arg(help = "Application name.")]
-    pub name: String,
-
-    #[arg(help = "Signal to send.")]
-    pub signal: u8,
+    // Clap does not allow an optional list of names before the signal.
+    #[arg(
+        value_name = "NAMES>... <SIGNAL",
+        help = "Application names or glob patterns such as 'lobby-*', followed by the signal to send.",
+        required = true
+    )]
+    pub args: Vec<String>,
+
+    #[arg(short, long, help = "Send the signal to all running applications.")]
+    pub all: bool,
+
+    #[arg(
+        long = "label",
+        value_delimiter = ',',
+        help = "Select the running applications with this label, can be used multiple times."
+    )]
+    pub labels: Vec<String>,
 }
 
 #[derive(Args)]
 #[command(about = "Send a stop command or a SIGTERM signal to the application subprocess.")]
 pub struct StopArgs {
-    #[arg(help = "Application name.")]
-    pub name: String,
+    #[arg(
+        help = "Application names or glob patterns such as 'lobby-*'.",
+        required_unless_present_any = ["all", "labels"]
+    )]
+    pub names: Vec<String>,
+
+    #[arg(short, long, help = "Stop all running applications.")]
+    pub all: bool,
+
+    #[arg(
+        long = "label",
+        value_delimiter = ',',
+        help = "Select the running applications with this label, can be used multiple times."
+    )]
+    pub labels: Vec<String>,
 
     #[arg(short, long, help = "Ignore 'stop_command' and send a SIGTERM signal.")]
     pub force: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "Seconds to wait before escalating to SIGTERM and then SIGKILL. Defaults to the profile 'stop_timeout' or 30."
+    )]
+    pub timeout: Option<u64>,
+
+    #[arg(long, help = "Return right after sending the stop command.")]
+    pub no_wait: bool,
 }
 
 impl SignalArgs {
     pub fn run(self) -> Result<()> {
-        application::check_app_exists(&self.name)?;
+        let (signal, names) = self.args.split_last().context("Signal not provided.")?;
 
-        if !application::app_already_running(&self.name)? {
-            return Err(anyhow!("Application not running."));
+        let signal = signal
+            .parse::<u8>()
+            .map_err(|err| anyhow!("Invalid signal '{signal}': {err}."))?;
+
+        if names.is_empty() && !self.all && self.labels.is_empty() {
+            return Err(anyhow!("Application name not provided."));
         }
 
-        let pids = application::app_pids_by_name(&self.name)?;
+        let names = application::resolve_app_names(names, self.all, &self.labels)?;
+        run_for_apps(names, |name| send_signal(name, signal))
+    }
+}
 
-        subprocess::send_unix_signal(pids[1], self.signal)?;
+fn send_signal(name: &String, signal: u8) -> Result<()> {
+    signal_app(name, signal)?;
+    println!("Signal sent.");
+    Ok(())
+}
 
-        println!("Signal sent.");
+pub fn signal_app(name: &String, signal: u8) -> Result<()> {
+    application::check_app_exists(name)?;
 
-        Ok(())
+    if !application::app_already_running(name)? {
+        return Err(Error::AppNotRunning.into());
     }
+
+    let pids = application::app_pids_by_name(name)?;
+
+    subprocess::send_unix_signal(pids[1], signal)
 }
 
-impl StopArgs {
-    pub fn run(self) -> Result<()> {
-        if self.force {
-            let signal = SignalArgs {
-                name: self.name,
-                signal: 15,
-            };
+pub fn check_stoppable(name: &String) -> Result<()> {
+    application::check_app_exists(name)?;
 
-            return signal.run();
-        }
+    if !application::app_already_running(name)? {
+        return Err(Error::AppNotRunning.into());
+    }
 
-        let mut app_dir = application::app_dir_by_name(&self.name)?;
+    Ok(application::require_capability(
+        name,
+        socket::CAPABILITY_STOP,
+    )?)
+}
 
-        app_dir.push(self.name.clone() + ".sock");
+impl StopArgs {
+    pub fn run(self) -> Result<()> {
+        let names = application::resolve_app_names(&self.names, self.all, &self.labels)?;
 
-        let mut stream = UnixStream::connect(app_dir)
-            .context(format!("Error connecting to '{}' socket.", self.name))?;
+        // Applications are stopped before the ones they depend on.
+        let names = match names.len() {
+            0 | 1 => names,
+            _ => dependencies::stop_order(&dependencies::running_dependencies(names))?,
+        };
 
-        let event = serde_json::to_vec(&SocketEvent::Stop)?;
+        run_for_apps(names, |name| self.stop(name))
+    }
 
-        stream.write_all(&event)?;
+    fn stop(&self, name: &String) -> Result<()> {
+        check_stoppable(name)?;
 
-        println!("Stop command sent.");
+        // Reported only once the request was sent, or answered when waiting.
+        if self.no_wait {
+            application::send_event(name, application::stop_event(self.force, self.timeout))?;
+            self.print_sent();
+            return Ok(());
+        }
 
+        let step = application::stop_app(name, self.force, self.timeout)?;
+        self.print_sent();
+        println!("{step}");
         Ok(())
     }
+
+    fn print_sent(&self) {
+        if self.force {
+            println!("Signal sent.");
+        } else {
+            println!("Stop command sent.");
+        }
+    }
 }
 
 #[derive(Args)]
 #[command(about = "Send a SIGKILL signal to the application subprocess.")]
 pub struct KillArgs {
-    #[arg(help = "Application name.")]
-    pub name: String,
+    #[arg(
+        help = "Application names or glob patterns such as 'lobby-*'.",
+        required_unless_present_any = ["all", "labels"]
+    )]
+    pub names: Vec<String>,
+
+    #[arg(short, long, help = "Kill all running applications.")]
+    pub all: bool,
+
+    #[arg(
+        long = "label",
+        value_delimiter = ',',
+        help = "Select the running applications with this label, can be used multiple times."
+    )]
+    pub labels: Vec<String>,
 }
 
 impl KillArgs {
     pub fn run(self) -> Result<()> {
-        let signal = SignalArgs {
-            name: self.name,
-            signal: 9,
-        };
-
-        signal.run()
+        let names = application::resolve_app_names(&self.names, self.all, &self.labels)?;
+        run_for_apps(names, |name| send_signal(name, 9))
     }
 }
 
@@ -96,7 +175,6 @@ impl KillArgs {
 mod tests {
     use super::*;
     extern crate test_utils;
-    use anyhow::Context;
     use std::{
         env,
         fs::{self, File},
@@ -108,8 +186,9 @@ mod tests {
     fn unit_signal_run() -> Result<()> {
         let name = "unit_signal_run".to_string();
         let command = SignalArgs {
-            name: name.clone(),
-            signal: 0,
+            args: vec![name.clone(), String::from("0")],
+            all: false,
+            labels: vec![],
         };
 
         let err = command.run().unwrap_err();
@@ -123,8 +202,9 @@ mod tests {
         let mut file = File::create(path)?;
 
         let command = SignalArgs {
-            name: name.clone(),
-            signal: 0,
+            args: vec![name.clone(), String::from("0")],
+            all: false,
+            labels: vec![],
         };
 
         let err = command.run().unwrap_err();
@@ -135,8 +215,9 @@ mod tests {
         writeln!(&mut file, "{}", pid)?;
 
         let command = SignalArgs {
-            name: name.clone(),
-            signal: 0,
+            args: vec![name.clone(), String::from("0")],
+            all: false,
+            labels: vec![],
         };
         let err = command.run().unwrap_err();
         assert_eq!(format!("{}", err), "Application not running.");
@@ -148,7 +229,14 @@ mod tests {
     #[test]
     fn unit_stop_run() -> Result<()> {
         let name = "unit_stop_run".to_string();
-        let command = StopArgs { name, force: true };
+        let command = StopArgs {
+            names: vec![name],
+            all: false,
+            labels: vec![],
+            force: true,
+            timeout: None,
+            no_wait: false,
+        };
         let err = command.run().unwrap_err();
         assert_eq!(format!("{}", err), "Application does not exist.");
         Ok(())
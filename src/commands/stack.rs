@@ -0,0 +1,295 @@
+use std::path::{Path, PathBuf};
+
+use super::{
+    signals::StopArgs,
+    start::{self, StartArgs},
+};
+use crate::{
+    application::{self, Application},
+    crescent::{self, Stack, StackApp},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Subcommand};
+use tabled::{settings::Style, Table, Tabled};
+
+#[derive(Args)]
+#[command(about = "Manage a group of applications described by a stack file.")]
+pub struct StackArgs {
+    #[command(subcommand)]
+    pub action: StackAction,
+}
+
+#[derive(Subcommand)]
+pub enum StackAction {
+    #[command(
+        about = "Start the applications of the stack that are not running, dependencies first."
+    )]
+    Up {
+        #[arg(help = "Stack name in the stacks directory or path to a stack file.")]
+        stack: String,
+    },
+    #[command(about = "Stop the running applications of the stack, dependents first.")]
+    Down {
+        #[arg(help = "Stack name in the stacks directory or path to a stack file.")]
+        stack: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Seconds to wait before escalating to SIGTERM and then SIGKILL. Defaults to the profile 'stop_timeout' or 30."
+        )]
+        timeout: Option<u64>,
+    },
+    #[command(about = "Print the status of every application of the stack.")]
+    Status {
+        #[arg(help = "Stack name in the stacks directory or path to a stack file.")]
+        stack: String,
+    },
+    #[command(about = "Stop the stack and start it again.")]
+    Restart {
+        #[arg(help = "Stack name in the stacks directory or path to a stack file.")]
+        stack: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Seconds to wait before escalating to SIGTERM and then SIGKILL. Defaults to the profile 'stop_timeout' or 30."
+        )]
+        timeout: Option<u64>,
+    },
+}
+
+#[derive(Tabled)]
+struct StackAppInfo {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Profile")]
+    profile: String,
+    #[tabled(rename = "Status")]
+    status: String,
+    #[tabled(rename = "Health")]
+    health: String,
+    #[tabled(rename = "Depends on")]
+    depends_on: String,
+}
+
+impl StackArgs {
+    pub fn run(self) -> Result<()> {
+        match self.action {
+            StackAction::Up { stack } => up(&stack),
+            StackAction::Down { stack, timeout } => down(&stack, timeout),
+            StackAction::Status { stack } => status(&stack),
+            StackAction::Restart { stack, timeout } => {
+                down(&stack, timeout)?;
+                println!();
+                up(&stack)
+            }
+        }
+    }
+}
+
+fn up(stack: &str) -> Result<()> {
+    let (path, stack) = load_stack(stack)?;
+
+    // Relative file paths are resolved from the directory of the stack file.
+    let stack_dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+    let apps = stack
+        .apps
+        .into_iter()
+        .map(|app| {
+            let name = app.name.clone();
+            into_start_args(app, &stack_dir)
+                .into_application()
+                .context(format!("Error loading '{name}'."))
+        })
+        .collect::<Result<Vec<Application>>>()?;
+
+    start::start_apps(apps)
+}
+
+fn down(stack: &str, timeout: Option<u64>) -> Result<()> {
+    let (_, stack) = load_stack(stack)?;
+
+    let mut names = vec![];
+
+    for app in stack.apps {
+        if application::app_already_running(&app.name)? {
+            names.push(app.name);
+        }
+    }
+
+    if names.is_empty() {
+        println!("No application running.");
+        return Ok(());
+    }
+
+    let stop = StopArgs {
+        names,
+        all: false,
+        labels: vec![],
+        force: false,
+        timeout,
+        no_wait: false,
+    };
+
+    stop.run()
+}
+
+fn status(stack: &str) -> Result<()> {
+    let (_, stack) = load_stack(stack)?;
+
+    let mut apps = vec![];
+
+    for app in stack.apps {
+        let status = if application::app_already_running(&app.name)? {
+            "Running"
+        } else if application::get_app_failed_state(&app.name)?.is_some() {
+            "Failed"
+        } else {
+            "Stopped"
+        };
+
+        let health = match status {
+            "Running" => match application::get_app_health(&app.name) {
+                Ok(Some(health)) => health.to_string(),
+                _ => String::from("N/A"),
+            },
+            _ => String::from("N/A"),
+        };
+
+        apps.push(StackAppInfo {
+            name: app.name,
+            profile: app.profile.unwrap_or(String::from("None")),
+            status: status.to_string(),
+            health,
+            depends_on: match app.depends_on {
+                Some(depends_on) if !depends_on.is_empty() => depends_on.join(", "),
+                _ => String::from("None"),
+            },
+        });
+    }
+
+    let mut table = Table::new(apps);
+    table.with(Style::modern());
+    println!("{table}");
+    Ok(())
+}
+
+fn load_stack(stack: &str) -> Result<(PathBuf, Stack)> {
+    let path = crescent::get_stack_path(stack)?;
+    let stack = crescent::get_stack(&path)?;
+
+    if stack.apps.is_empty() {
+        return Err(anyhow!("Stack has no applications."));
+    }
+
+    for (i, app) in stack.apps.iter().enumerate() {
+        if stack.apps[..i].iter().any(|other| other.name == app.name) {
+            return Err(anyhow!("'{}' is listed more than once.", app.name));
+        }
+    }
+
+    Ok((path, stack))
+}
+
+// The stack fields are passed the same way as command line arguments, overwriting the profile.
+fn into_start_args(app: StackApp, stack_dir: &Path) -> StartArgs {
+    let relative_to_stack = |path: String| stack_dir.join(path).to_string_lossy().to_string();
+
+    let env = app.env.map(|env| {
+        env.into_iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect()
+    });
+
+    StartArgs {
+        file_path: app.file_path.map(relative_to_stack),
+        name: Some(app.name),
+        interpreter: app.interpreter,
+        interpreter_arguments: app.interpreter_arguments,
+        application_arguments: app.application_arguments,
+        profile: app.profile,
+        restart_policy: app.restart_policy,
+        restart_delay: app.restart_delay,
+        max_restarts: app.max_restarts,
+        crash_loop_limit: app.crash_loop_limit,
+        crash_loop_window: app.crash_loop_window,
+        depends_on: app.depends_on,
+        labels: app.labels,
+        env,
+        env_file: app.env_file.map(relative_to_stack),
+        clean_env: app.clean_env.unwrap_or(false),
+        path: app.path,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::BTreeMap, env, fs};
+
+    #[test]
+    fn unit_stack_start_args() -> Result<()> {
+        let app = StackApp {
+            name: String::from("lobby"),
+            profile: Some(String::from("example")),
+            file_path: Some(String::from("servers/lobby.jar")),
+            depends_on: Some(vec![String::from("database")]),
+            env: Some(BTreeMap::from([(
+                String::from("LEVEL"),
+                String::from("lobby"),
+            )])),
+            env_file: Some(String::from("lobby.env")),
+            ..Default::default()
+        };
+
+        let start_args = into_start_args(app, Path::new("/srv/network"));
+        assert_eq!(
+            start_args.file_path,
+            Some(String::from("/srv/network/servers/lobby.jar"))
+        );
+        assert_eq!(start_args.name, Some(String::from("lobby")));
+        assert_eq!(start_args.profile, Some(String::from("example")));
+        assert_eq!(start_args.depends_on, Some(vec![String::from("database")]));
+        assert_eq!(start_args.env, Some(vec![String::from("LEVEL=lobby")]));
+        assert_eq!(
+            start_args.env_file,
+            Some(String::from("/srv/network/lobby.env"))
+        );
+
+        let app = StackApp {
+            name: String::from("proxy"),
+            file_path: Some(String::from("/opt/velocity.jar")),
+            ..Default::default()
+        };
+
+        let start_args = into_start_args(app, Path::new("/srv/network"));
+        assert_eq!(
+            start_args.file_path,
+            Some(String::from("/opt/velocity.jar"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unit_stack_load() -> Result<()> {
+        let err = load_stack("unit_stack_load_does_not_exist").unwrap_err();
+        assert_eq!(format!("{}", err), "Stack not found.");
+
+        let path = env::temp_dir().join("unit_stack_load.json");
+
+        fs::write(&path, r#"{"apps": []}"#)?;
+        let err = load_stack(path.to_str().unwrap()).unwrap_err();
+        assert_eq!(format!("{}", err), "Stack has no applications.");
+
+        fs::write(&path, r#"{"apps": [{"name": "lobby"}, {"name": "lobby"}]}"#)?;
+        let err = load_stack(path.to_str().unwrap()).unwrap_err();
+        assert_eq!(format!("{}", err), "'lobby' is listed more than once.");
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
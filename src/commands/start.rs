@@ -1,18 +1,27 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fs::{self, File},
+    io::{self, Write},
     path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
 };
 
 use super::save::SaveFile;
 use crate::{
-    application::{self, Application},
+    application::{self, Application, RestartPolicy},
     crescent::{self, Profile},
-    logger, subprocess, util,
+    daemon::{self, DaemonEvent},
+    dependencies, environment,
+    error::Error,
+    history::{self, StoppedBy},
+    logger, schedule, subprocess, util,
 };
 
 use anyhow::{anyhow, Context, Result};
-use clap::{Args, ValueHint};
-use daemonize::Daemonize;
+use clap::{Args, ValueEnum, ValueHint};
+use daemonize::{Daemonize, Outcome};
 use log::LevelFilter;
 use serde::{Deserialize, Serialize};
 
@@ -58,8 +67,102 @@ pub struct StartArgs {
     )]
     pub profile: Option<String>,
 
+    #[arg(
+        long = "restart",
+        value_enum,
+        help = "Restart the application when it exits. Defaults to never."
+    )]
+    pub restart_policy: Option<RestartPolicy>,
+
+    #[arg(
+        long = "restart-delay",
+        help = "Seconds to wait before restarting, doubles after every attempt. Defaults to 1."
+    )]
+    pub restart_delay: Option<u64>,
+
+    #[arg(
+        long = "max-restarts",
+        help = "Maximum restarts in a row, reset after the application stays up for a minute."
+    )]
+    pub max_restarts: Option<u32>,
+
+    #[arg(
+        long = "crash-loop-limit",
+        help = "Exits within the crash loop window that mark the application as failed. Defaults to 5."
+    )]
+    pub crash_loop_limit: Option<u32>,
+
+    #[arg(
+        long = "crash-loop-window",
+        help = "Seconds in which exits are counted towards the crash loop limit. Defaults to 60."
+    )]
+    pub crash_loop_window: Option<u64>,
+
+    #[arg(
+        long = "depends-on",
+        value_delimiter = ',',
+        help = "Applications that have to be running first, separated by commas. Overrides the profile 'depends_on'."
+    )]
+    pub depends_on: Option<Vec<String>>,
+
+    #[arg(
+        long = "label",
+        value_delimiter = ',',
+        help = "Label to select the application with in bulk commands, can be used multiple times. Overrides the profile 'labels'."
+    )]
+    pub labels: Option<Vec<String>>,
+
+    #[arg(
+        short = 'e',
+        long = "env",
+        value_name = "KEY=VALUE",
+        help = "Environment variable for the application, can be used multiple times. Overwrites the profile 'env'."
+    )]
+    pub env: Option<Vec<String>>,
+
+    #[arg(
+        long = "env-file",
+        value_hint = ValueHint::FilePath,
+        help = "Dotenv file to load environment variables from."
+    )]
+    pub env_file: Option<String>,
+
+    #[arg(
+        long = "clean-env",
+        help = "Start the application without inheriting the environment."
+    )]
+    #[serde(default)]
+    pub clean_env: bool,
+
+    #[arg(
+        long = "path",
+        help = "PATH of the application, also used to find the interpreter or executable."
+    )]
+    pub path: Option<String>,
+
+    // The environment resolved by `start --saved` or `stack up`, sent as JSON.
+    #[arg(long = "env-stdin", hide = true)]
+    #[serde(skip)]
+    pub env_stdin: bool,
+
     #[arg(short, long, help = "Start all saved apps.")]
     pub saved: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "Wait until the application is ready, exiting with an error if it exits first."
+    )]
+    #[serde(skip)]
+    pub wait: bool,
+
+    #[arg(
+        long,
+        requires = "wait",
+        help = "Seconds to wait for the application to be ready. Defaults to 120."
+    )]
+    #[serde(skip)]
+    pub timeout: Option<u64>,
 }
 
 impl From<Profile> for StartArgs {
@@ -71,33 +174,106 @@ impl From<Profile> for StartArgs {
             interpreter_arguments: profile.interpreter_arguments,
             application_arguments: profile.application_arguments,
             profile: None,
-            saved: false,
+            restart_policy: profile.restart_policy,
+            restart_delay: profile.restart_delay,
+            max_restarts: profile.max_restarts,
+            crash_loop_limit: profile.crash_loop_limit,
+            crash_loop_window: profile.crash_loop_window,
+            depends_on: profile.depends_on,
+            labels: profile.labels,
+            env_file: profile.env_file,
+            clean_env: profile.clean_env.unwrap_or(false),
+            path: profile.path,
+            ..Default::default()
         }
     }
 }
 
 static LOGGER: logger::Logger = logger::Logger;
 
-impl StartArgs {
-    pub fn run(mut self) -> Result<()> {
-        if !cfg!(test) {
-            log::set_logger(&LOGGER).unwrap();
-            log::set_max_level(LevelFilter::Info);
-        }
+const DEFAULT_WAIT_TIMEOUT: u64 = 120;
 
+impl StartArgs {
+    pub(crate) fn run(self) -> Result<()> {
         if self.saved {
             return start_saved();
         }
 
-        let stop_command = match &self.profile {
+        let wait = match self.wait {
+            true => Some(Duration::from_secs(
+                self.timeout.unwrap_or(DEFAULT_WAIT_TIMEOUT),
+            )),
+            false => None,
+        };
+
+        let app_info = self.into_application()?;
+
+        if application::app_already_running(&app_info.name)? {
+            return Err(Error::AlreadyRunning.into());
+        }
+
+        // `start --saved` starts the dependencies first, otherwise they have to be running.
+        if let Some(depends_on) = &app_info.depends_on {
+            dependencies::check_missing(&[(app_info.name.clone(), depends_on.clone())])?;
+        }
+
+        start(app_info, wait)
+    }
+
+    /// Loads the profile, if any, and builds the `Application` that will be supervised.
+    pub(crate) fn into_application(mut self) -> Result<Application> {
+        let profile_name = self.profile.clone();
+
+        // Kept without the profile fields, restarting with a profile merges it over them again.
+        let mut cli_args = None;
+
+        // Fields only set by profiles are empty when starting without one.
+        let mut profile = match &self.profile {
             Some(profile_str) => {
                 let profile = crescent::get_profile(profile_str)?;
+                cli_args = Some(self.clone());
                 self = self.overwrite_args(profile.clone().into())?;
-                profile.stop_command
+                profile
             }
-            None => None,
+            None => Profile::default(),
         };
 
+        if let Some(health_check) = &profile.health_check {
+            health_check.validate()?;
+        }
+
+        if let Some(readiness) = &profile.readiness {
+            readiness.validate()?;
+        }
+
+        if let Some(schedules) = &profile.schedules {
+            schedule::validate(schedules)?;
+        }
+
+        if let Some(scheduled_restart) = &profile.scheduled_restart {
+            scheduled_restart.validate()?;
+        }
+
+        // The supervisor does not run in the same directory, keeping the absolute path.
+        if let Some(remote) = &mut profile.remote {
+            remote.validate()?;
+            remote.token_file = fs::canonicalize(&remote.token_file)?;
+        }
+
+        let env = match self.env_stdin {
+            true => Some(environment::read_vars(io::stdin())?),
+            false => environment::resolve(
+                self.env_file.as_deref(),
+                profile.env.as_ref(),
+                self.env.as_deref(),
+            )?,
+        };
+
+        // Saved applications read the env file again from a different directory.
+        if let (Some(env_file), false) = (&mut self.env_file, self.env_stdin) {
+            *env_file = fs::canonicalize(&*env_file)?.to_string_lossy().to_string();
+        }
+
         let path = match &self.file_path {
             Some(path) => path,
             None => return Err(anyhow!("Executable path not provided.")),
@@ -117,13 +293,20 @@ impl StartArgs {
             return Err(anyhow!("Name contains whitespace."));
         }
 
-        if application::app_already_running(&name)? {
-            return Err(anyhow!(
-                "An application with the same name is already running."
-            ));
-        }
+        // Keeping the profile name so the app can be restarted or saved with it.
+        let mut start_args = self.clone();
+        start_args.profile = profile_name;
 
-        let start_args = self.clone();
+        // The path given on the command line is relative to the directory it was started in.
+        if let Some(cli_args) = &mut cli_args {
+            if cli_args.file_path.is_some() {
+                cli_args.file_path = Some(file_path.to_string_lossy().to_string());
+            }
+
+            if cli_args.env_file.is_some() {
+                cli_args.env_file = self.env_file.clone();
+            }
+        }
 
         let (interpreter_args, application_args) = self.create_subprocess_arguments(&file_path);
 
@@ -135,15 +318,26 @@ impl StartArgs {
             cmd = i_args
         }
 
-        let app_info = Application {
+        Ok(Application {
             name,
             cmd,
             file_path,
-            stop_command,
+            stop_command: profile.stop_command,
+            stop_timeout: profile.stop_timeout,
+            health_check: profile.health_check,
+            readiness: profile.readiness,
+            schedules: profile.schedules,
+            scheduled_restart: profile.scheduled_restart,
+            remote: profile.remote,
+            access: profile.access,
+            depends_on: start_args.depends_on.clone(),
+            labels: start_args.labels.clone(),
+            env,
+            clean_env: start_args.clean_env,
+            path: start_args.path.clone(),
             start_args,
-        };
-
-        start(app_info)
+            cli_args,
+        })
     }
 
     fn overwrite_args(self, loaded_args: StartArgs) -> Result<StartArgs> {
@@ -197,7 +391,19 @@ impl StartArgs {
             interpreter_arguments,
             application_arguments,
             profile: None,
-            saved: false,
+            restart_policy: self.restart_policy.or(loaded_args.restart_policy),
+            restart_delay: self.restart_delay.or(loaded_args.restart_delay),
+            max_restarts: self.max_restarts.or(loaded_args.max_restarts),
+            crash_loop_limit: self.crash_loop_limit.or(loaded_args.crash_loop_limit),
+            crash_loop_window: self.crash_loop_window.or(loaded_args.crash_loop_window),
+            depends_on: self.depends_on.or(loaded_args.depends_on),
+            labels: self.labels.or(loaded_args.labels),
+            env: self.env,
+            env_file: self.env_file.or(loaded_args.env_file),
+            clean_env: self.clean_env || loaded_args.clean_env,
+            path: self.path.or(loaded_args.path),
+            env_stdin: self.env_stdin,
+            ..Default::default()
         })
     }
 
@@ -212,10 +418,7 @@ impl StartArgs {
             }
         };
 
-        let application_args = match self.application_arguments {
-            Some(args) => args,
-            None => vec![],
-        };
+        let application_args = self.application_arguments.unwrap_or_default();
 
         interpreter_args.push(exec_path.to_str().unwrap().to_string());
 
@@ -233,9 +436,44 @@ fn start_saved() -> Result<()> {
         return Err(anyhow!("List of apps is empty."));
     }
 
+    let mut apps = save_file.apps;
+
+    // The variables from the env file were not saved, reading it again.
+    for app_info in &mut apps {
+        if let Some(env_file) = &app_info.start_args.env_file {
+            app_info.env = environment::resolve(Some(env_file), app_info.env.as_ref(), None)?;
+        }
+    }
+
+    start_apps(apps)
+}
+
+/// Starts every application in its own process, after the ones it depends on are ready.
+/// Already running applications are skipped.
+pub fn start_apps(app_infos: Vec<Application>) -> Result<()> {
+    let apps: Vec<(String, Vec<String>)> = app_infos
+        .iter()
+        .map(|app_info| {
+            let depends_on = app_info.depends_on.clone().unwrap_or_default();
+            (app_info.name.clone(), depends_on)
+        })
+        .collect();
+
+    dependencies::check_missing(&apps)?;
+    let order = dependencies::start_order(&apps)?;
+
+    // Applications others depend on are waited for until they are ready.
+    let dependencies: BTreeSet<&String> = apps.iter().flat_map(|(_, deps)| deps).collect();
+    let mut failed: Vec<String> = vec![];
+
     util::print_title_cyan("Starting applications.");
 
-    for app_info in save_file.apps {
+    for name in order {
+        let app_info = match app_infos.iter().find(|app_info| app_info.name == name) {
+            Some(app_info) => app_info.clone(),
+            None => continue,
+        };
+
         if application::app_already_running(&app_info.name)? {
             println!(
                 "An application with the name '{}' is already running. Skipping.",
@@ -245,6 +483,14 @@ fn start_saved() -> Result<()> {
             continue;
         }
 
+        let depends_on = app_info.depends_on.clone().unwrap_or_default();
+
+        if let Some(dependency) = depends_on.iter().find(|dep| failed.contains(dep)) {
+            println!("'{name}' depends on '{dependency}', which failed to start. Skipping.");
+            failed.push(name);
+            continue;
+        }
+
         let exec_path = util::get_exec_path();
         let mut cmd = util::get_base_command(exec_path);
         let mut cmd_args = vec![];
@@ -262,14 +508,13 @@ fn start_saved() -> Result<()> {
             cmd_args.push(interpreter);
         }
 
-        if let Some(args) = app_info.start_args.interpreter_arguments {
-            cmd_args.push("--interpreter-args".to_string());
-            cmd_args.push(args.join(" "));
+        // Passed one by one, arguments can contain spaces.
+        for arg in app_info.start_args.interpreter_arguments.iter().flatten() {
+            cmd_args.push(format!("--interpreter-args={arg}"));
         }
 
-        if let Some(args) = app_info.start_args.application_arguments {
-            cmd_args.push("--arguments".to_string());
-            cmd_args.push(args.join(" "));
+        for arg in app_info.start_args.application_arguments.iter().flatten() {
+            cmd_args.push(format!("--arguments={arg}"));
         }
 
         if let Some(profile) = app_info.start_args.profile {
@@ -277,39 +522,214 @@ fn start_saved() -> Result<()> {
             cmd_args.push(profile);
         }
 
-        cmd.args(cmd_args).spawn()?;
+        if let Some(policy) = app_info.start_args.restart_policy {
+            cmd_args.push("--restart".to_string());
+            cmd_args.push(policy.to_possible_value().unwrap().get_name().to_string());
+        }
+
+        if let Some(delay) = app_info.start_args.restart_delay {
+            cmd_args.push("--restart-delay".to_string());
+            cmd_args.push(delay.to_string());
+        }
+
+        if let Some(max_restarts) = app_info.start_args.max_restarts {
+            cmd_args.push("--max-restarts".to_string());
+            cmd_args.push(max_restarts.to_string());
+        }
+
+        if let Some(limit) = app_info.start_args.crash_loop_limit {
+            cmd_args.push("--crash-loop-limit".to_string());
+            cmd_args.push(limit.to_string());
+        }
+
+        if let Some(window) = app_info.start_args.crash_loop_window {
+            cmd_args.push("--crash-loop-window".to_string());
+            cmd_args.push(window.to_string());
+        }
+
+        if let Some(depends_on) = app_info.start_args.depends_on {
+            cmd_args.push("--depends-on".to_string());
+            cmd_args.push(depends_on.join(","));
+        }
+
+        if let Some(labels) = app_info.start_args.labels {
+            cmd_args.push("--label".to_string());
+            cmd_args.push(labels.join(","));
+        }
+
+        if app_info.clean_env {
+            cmd_args.push("--clean-env".to_string());
+        }
+
+        if let Some(path) = app_info.path {
+            cmd_args.push("--path".to_string());
+            cmd_args.push(path);
+        }
+
+        // Only kept to be saved again, the variables are sent with the environment.
+        if let Some(env_file) = app_info.start_args.env_file {
+            cmd_args.push("--env-file".to_string());
+            cmd_args.push(env_file);
+        }
+
+        if !dependencies.contains(&name) {
+            cmd.args(cmd_args);
+            spawn_start(cmd, &app_info.env)?;
+            continue;
+        }
+
+        cmd_args.push("--wait".to_string());
+        cmd.args(cmd_args);
+
+        if !spawn_start(cmd, &app_info.env)?.wait()?.success() {
+            failed.push(name);
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(anyhow!(
+            "Applications failed to start: {}.",
+            failed.join(", ")
+        ));
     }
 
     Ok(())
 }
 
-pub fn start(app_info: Application) -> Result<()> {
+// Variables are sent through stdin so their values are not in the command line of the supervisor.
+fn spawn_start(mut cmd: Command, env: &Option<BTreeMap<String, String>>) -> Result<Child> {
+    let env = match env {
+        Some(env) => env,
+        None => return Ok(cmd.spawn()?),
+    };
+
+    let mut child = cmd.arg("--env-stdin").stdin(Stdio::piped()).spawn()?;
+
+    // Closing stdin once written, the variables are read until the end.
+    // A child that failed before reading them reports its own error.
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(&serde_json::to_vec(env)?);
+    }
+
+    Ok(child)
+}
+
+/// Daemonizes and supervises the application, the caller only returns if `wait` is set.
+/// Applications are supervised by the daemon instead if it is running.
+pub fn start(app_info: Application, wait: Option<Duration>) -> Result<()> {
     let app_dir = application::app_dir_by_name(&app_info.name)?;
 
     if app_dir.exists() {
-        fs::remove_dir_all(&app_dir).context("Error resetting application directory.")?;
+        history::reset_app_dir(&app_dir, &app_info.name)
+            .context("Error resetting application directory.")?;
     }
 
     fs::create_dir_all(&app_dir).context("Error creating application directory.")?;
 
     eprintln!("Starting '{}' application.", app_info.name);
 
+    // Runs from before this start are skipped when waiting, their timestamps can share the same second.
+    let previous_runs = history::get_app_history(&app_info.name)?.len();
+
+    // The daemon supervises the application from its own process if it is running.
+    if daemon::request(DaemonEvent::Start(Box::new(app_info.clone())))?.is_some() {
+        return match wait {
+            Some(timeout) => wait_app_ready(&app_info, previous_runs, timeout),
+            None => Ok(()),
+        };
+    }
+
+    if !cfg!(test) {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(LevelFilter::Info);
+    }
+
     {
         let log = File::create(app_dir.join(app_info.name.clone() + ".log"))?;
         let pid_path = app_dir.join(app_info.name.clone() + ".pid");
-        let work_dir = app_info.file_path.parent().unwrap().to_path_buf();
+        let work_dir = app_info.work_dir();
 
         let daemonize = Daemonize::new()
             .pid_file(pid_path)
             .working_directory(work_dir)
             .stderr(log);
 
-        daemonize.start()?;
+        match wait {
+            Some(timeout) => match daemonize.execute() {
+                Outcome::Parent(result) => {
+                    result?;
+                    return wait_app_ready(&app_info, previous_runs, timeout);
+                }
+                Outcome::Child(result) => {
+                    result?;
+                }
+            },
+            None => daemonize.start()?,
+        }
     }
 
     subprocess::start(app_info, app_dir)
 }
 
+// Polls the readiness condition, or just the socket if there is none,
+// returning an error if the subprocess exits or the supervisor gives up first.
+fn wait_app_ready(app_info: &Application, previous_runs: usize, timeout: Duration) -> Result<()> {
+    let name = &app_info.name;
+    let log_path = application::app_dir_by_name(name)?.join(name.clone() + ".log");
+    let work_dir = app_info.work_dir();
+
+    match &app_info.readiness {
+        Some(readiness) => eprintln!("Waiting for '{name}' to be ready, {readiness}."),
+        None => eprintln!("Waiting for '{name}' to be ready."),
+    }
+
+    let start = Instant::now();
+
+    loop {
+        // A scheduled restart can stop the subprocess before it is ready, it is started again.
+        let exit = history::get_app_history(name)?
+            .into_iter()
+            .skip(previous_runs)
+            .filter_map(|run| run.exit)
+            .find(|exit| exit.stopped_by != Some(StoppedBy::ScheduledRestart));
+
+        if let Some(exit) = exit {
+            return Err(anyhow!(
+                "Application exited with {} before becoming ready.",
+                exit.status()
+            ));
+        }
+
+        if let Some(failed) = application::get_app_failed_state(name)? {
+            return Err(anyhow!(
+                "Application failed before becoming ready: {}",
+                failed.reason
+            ));
+        }
+
+        if application::app_already_running(name)? {
+            let ready = match &app_info.readiness {
+                Some(readiness) => readiness.is_ready(&log_path, &work_dir)?,
+                None => true,
+            };
+
+            if ready {
+                println!("Application ready.");
+                return Ok(());
+            }
+        }
+
+        if start.elapsed() >= timeout {
+            return Err(anyhow!(
+                "Application was not ready after {} seconds.",
+                timeout.as_secs()
+            ));
+        }
+
+        thread::sleep(Duration::from_millis(250));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,7 +744,7 @@ mod tests {
             interpreter_arguments: None,
             application_arguments: None,
             profile: None,
-            saved: false,
+            ..Default::default()
         };
 
         let err = start_command.run().unwrap_err();
@@ -337,7 +757,7 @@ mod tests {
             interpreter_arguments: None,
             application_arguments: None,
             profile: None,
-            saved: false,
+            ..Default::default()
         };
 
         let err = start_command.run().unwrap_err();
@@ -353,7 +773,7 @@ mod tests {
             interpreter_arguments: None,
             application_arguments: None,
             profile: None,
-            saved: false,
+            ..Default::default()
         };
 
         let err = start_command.run().unwrap_err();
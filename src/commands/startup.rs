@@ -0,0 +1,301 @@
+use std::{
+    env,
+    ffi::{CStr, CString},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::util;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Subcommand};
+
+#[derive(Args)]
+#[command(about = "Start the saved applications on boot with a systemd unit.")]
+pub struct StartupArgs {
+    #[command(subcommand)]
+    pub action: StartupAction,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Use a system unit instead of a user unit, running as the user that ran sudo. Requires root."
+    )]
+    pub system: bool,
+
+    #[arg(
+        long,
+        global = true,
+        requires = "system",
+        help = "Name or uid of the user the system unit runs as, needed to run it as root."
+    )]
+    pub user: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum StartupAction {
+    #[command(about = "Write and enable the unit.")]
+    Install,
+    #[command(about = "Disable and remove the unit.")]
+    Uninstall,
+    #[command(about = "Print the unit without installing it.")]
+    Print,
+}
+
+const UNIT_NAME: &str = "crescent.service";
+
+// The user the unit runs as and their home, where the saved applications are.
+#[derive(Debug)]
+struct UnitUser {
+    uid: Option<u32>,
+    home: String,
+}
+
+// Every application is stopped one after the other, each stop can take a while.
+const STOP_TIMEOUT: u64 = 600;
+
+impl StartupArgs {
+    pub fn run(self) -> Result<()> {
+        match self.action {
+            StartupAction::Install => install(self.system, self.user.as_deref()),
+            StartupAction::Uninstall => uninstall(self.system),
+            StartupAction::Print => {
+                let user = unit_user(self.system, self.user.as_deref())?;
+                print!("{}", build_unit(&user));
+                Ok(())
+            }
+        }
+    }
+}
+
+fn install(system: bool, user: Option<&str>) -> Result<()> {
+    let user = unit_user(system, user)?;
+    let path = unit_path(system)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("Error creating unit directory.")?;
+    }
+
+    fs::write(&path, build_unit(&user))
+        .context(format!("Error writing unit to '{}'.", path.display()))?;
+
+    println!("Unit written to '{}'.", path.display());
+
+    systemctl(system, &["daemon-reload"])?;
+    systemctl(system, &["enable", UNIT_NAME])?;
+
+    println!("Unit enabled, saved applications will start on boot.");
+
+    if !Path::new(&user.home).join(".crescent/apps.json").exists() {
+        println!("No applications saved yet, use 'cres save' after starting them.");
+    }
+
+    // Without lingering, user units only start once the user logs in.
+    if !system {
+        println!("Run 'loginctl enable-linger' to start them before logging in.");
+    }
+
+    Ok(())
+}
+
+fn uninstall(system: bool) -> Result<()> {
+    let path = unit_path(system)?;
+
+    if !path.exists() {
+        return Err(anyhow!("Unit not installed."));
+    }
+
+    systemctl(system, &["disable", UNIT_NAME])?;
+
+    fs::remove_file(&path).context(format!("Error removing '{}'.", path.display()))?;
+    systemctl(system, &["daemon-reload"])?;
+
+    println!("Unit removed.");
+    Ok(())
+}
+
+fn unit_path(system: bool) -> Result<PathBuf> {
+    if system {
+        return Ok(PathBuf::from("/etc/systemd/system").join(UNIT_NAME));
+    }
+
+    let home = env::var("HOME").context("Error getting HOME env.")?;
+    Ok(PathBuf::from(home)
+        .join(".config/systemd/user")
+        .join(UNIT_NAME))
+}
+
+fn systemctl(system: bool, args: &[&str]) -> Result<()> {
+    let mut args = args.to_vec();
+
+    if !system {
+        args.insert(0, "--user");
+    }
+
+    let command = format!("systemctl {}", args.join(" "));
+
+    let status = Command::new("systemctl")
+        .args(args)
+        .status()
+        .context(format!("Error running '{command}'."))?;
+
+    if !status.success() {
+        return Err(anyhow!("'{command}' exited with {status}."));
+    }
+
+    Ok(())
+}
+
+fn build_unit(user: &UnitUser) -> String {
+    // Interpreters are found with the PATH of whoever installed the unit.
+    let path = env::var("PATH").unwrap_or_default();
+
+    unit(&util::get_exec_path(), &user.home, &path, user.uid)
+}
+
+// `--system` runs under sudo, the unit runs as the user that ran it instead of root,
+// root has to be asked for with `--user`.
+fn unit_user(system: bool, user: Option<&str>) -> Result<UnitUser> {
+    if !system {
+        let home = env::var("HOME").context("Error getting HOME env.")?;
+        return Ok(UnitUser { uid: None, home });
+    }
+
+    if let Some(user) = user {
+        let entry = match user.parse() {
+            Ok(uid) => lookup_uid(uid),
+            Err(_) => lookup_name(user),
+        };
+
+        return entry.ok_or_else(|| anyhow!("User '{user}' not found."));
+    }
+
+    let uid = match env::var("SUDO_UID") {
+        Ok(uid) => uid.parse().context("Invalid SUDO_UID env.")?,
+        Err(_) => unsafe { libc::geteuid() },
+    };
+
+    if uid == 0 {
+        return Err(anyhow!(
+            "The unit would run as root, use '--user root' if that is intended."
+        ));
+    }
+
+    lookup_uid(uid).ok_or_else(|| anyhow!("User with uid {uid} not found."))
+}
+
+fn lookup_uid(uid: u32) -> Option<UnitUser> {
+    passwd_user(unsafe { libc::getpwuid(uid) })
+}
+
+fn lookup_name(name: &str) -> Option<UnitUser> {
+    let name = CString::new(name).ok()?;
+    passwd_user(unsafe { libc::getpwnam(name.as_ptr()) })
+}
+
+fn passwd_user(entry: *mut libc::passwd) -> Option<UnitUser> {
+    if entry.is_null() {
+        return None;
+    }
+
+    let (uid, home) = unsafe { ((*entry).pw_uid, CStr::from_ptr((*entry).pw_dir)) };
+
+    Some(UnitUser {
+        uid: Some(uid),
+        home: home.to_string_lossy().to_string(),
+    })
+}
+
+// Starts the saved applications once and stops every application before the shutdown,
+// supervisors left in the unit cgroup are killed by systemd afterwards.
+fn unit(exec_path: &Path, home: &str, path: &str, user: Option<u32>) -> String {
+    let exec_path = exec_path.display();
+
+    let mut unit = String::from("[Unit]\nDescription=crescent applications\n");
+
+    // The user manager has no network target to wait for.
+    if user.is_some() {
+        unit.push_str("After=network-online.target\nWants=network-online.target\n");
+    }
+
+    unit.push_str(&format!(
+        "
+[Service]
+Type=oneshot
+RemainAfterExit=yes
+Environment=\"HOME={home}\"
+Environment=\"PATH={path}\"
+"
+    ));
+
+    if let Some(uid) = user {
+        unit.push_str(&format!("User={uid}\n"));
+    }
+
+    let wanted_by = match user {
+        Some(_) => "multi-user.target",
+        None => "default.target",
+    };
+
+    unit.push_str(&format!(
+        "ExecStart=\"{exec_path}\" start --saved
+ExecStop=\"{exec_path}\" stop --all
+TimeoutStopSec={STOP_TIMEOUT}
+
+[Install]
+WantedBy={wanted_by}
+"
+    ));
+
+    unit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_startup_unit() {
+        let exec_path = PathBuf::from("/usr/local/bin/cres");
+
+        let user_unit = unit(&exec_path, "/home/crescent", "/usr/bin:/bin", None);
+        assert!(user_unit.contains("Environment=\"HOME=/home/crescent\"\n"));
+        assert!(user_unit.contains("Environment=\"PATH=/usr/bin:/bin\"\n"));
+        assert!(user_unit.contains("ExecStart=\"/usr/local/bin/cres\" start --saved\n"));
+        assert!(user_unit.contains("ExecStop=\"/usr/local/bin/cres\" stop --all\n"));
+        assert!(user_unit.contains("WantedBy=default.target\n"));
+        assert!(!user_unit.contains("User="));
+        assert!(!user_unit.contains("After="));
+
+        let system_unit = unit(&exec_path, "/home/crescent", "/usr/bin:/bin", Some(1000));
+        assert!(system_unit.contains("User=1000\n"));
+        assert!(system_unit.contains("After=network-online.target\n"));
+        assert!(system_unit.contains("WantedBy=multi-user.target\n"));
+    }
+
+    #[test]
+    fn unit_startup_unit_user() -> Result<()> {
+        let user = unit_user(true, Some("root"))?;
+        assert_eq!(user.uid, Some(0));
+        assert_eq!(user.home, "/root");
+
+        let user = unit_user(true, Some("0"))?;
+        assert_eq!(user.uid, Some(0));
+
+        let err = unit_user(true, Some("crescent_does_not_exist")).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "User 'crescent_does_not_exist' not found."
+        );
+
+        env::set_var("SUDO_UID", "0");
+        let err = unit_user(true, None).unwrap_err();
+        env::remove_var("SUDO_UID");
+        assert_eq!(
+            format!("{}", err),
+            "The unit would run as root, use '--user root' if that is intended."
+        );
+        Ok(())
+    }
+}
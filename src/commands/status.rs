@@ -1,10 +1,19 @@
-use std::println;
+use std::{collections::BTreeMap, println};
 
-use crate::{application, util};
+use crate::{
+    application::{self, FailedState},
+    environment,
+    health::HealthStatus,
+    history, remote,
+    schedule::{self, ScheduledRestart},
+    socket::Hello,
+    util,
+};
 
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Local, TimeZone, Utc};
 use clap::Args;
+use crossterm::style::Stylize;
 use sysinfo::System;
 
 #[derive(Args)]
@@ -18,6 +27,17 @@ impl StatusArgs {
     pub fn run(self) -> Result<()> {
         application::check_app_exists(&self.name)?;
 
+        if remote::get().is_some() {
+            return print_remote_status(&self.name);
+        }
+
+        if !application::app_already_running(&self.name)? {
+            if let Some(failed) = application::get_app_failed_state(&self.name)? {
+                print_failed_state(&self.name, failed);
+                return Ok(());
+            }
+        }
+
         let pids = application::app_pids_by_name(&self.name)?;
 
         let status = application::get_app_info(&self.name)?;
@@ -48,6 +68,34 @@ impl StatusArgs {
             "Profile",
             status.start_args.profile.unwrap_or(String::new()),
         );
+        util::println_field_white(
+            "Restart policy",
+            format!("{:?}", status.start_args.restart_policy.unwrap_or_default()),
+        );
+
+        if let Some(depends_on) = &status.depends_on {
+            util::println_field_white("Depends on", depends_on.join(", "));
+        }
+
+        if let Some(labels) = &status.labels {
+            util::println_field_white("Labels", labels.join(", "));
+        }
+
+        print_last_exit(&self.name);
+
+        if let Ok(hello) = application::get_app_hello(&self.name) {
+            print_version(hello);
+        }
+
+        if let Ok(Some(health)) = application::get_app_health(&self.name) {
+            print_health(health);
+        }
+
+        if status.schedules.is_some() || status.scheduled_restart.is_some() {
+            print_schedules(&status.schedules, &status.scheduled_restart);
+        }
+
+        print_environment(&status.env, status.clean_env, &status.path);
 
         println!();
 
@@ -94,3 +142,176 @@ impl StatusArgs {
         Ok(())
     }
 }
+
+// Only what the supervisor sends is available, processes and files are on the other host.
+fn print_remote_status(name: &String) -> Result<()> {
+    let status = application::get_app_info(name)?;
+
+    util::print_title_cyan("Application information");
+
+    util::println_field_white("Name", &status.name);
+    util::println_field_white("Remote", remote::get().unwrap().address.as_str());
+    util::println_field_white(
+        "Profile",
+        status.start_args.profile.unwrap_or(String::new()),
+    );
+    util::println_field_white(
+        "Restart policy",
+        format!("{:?}", status.start_args.restart_policy.unwrap_or_default()),
+    );
+
+    if let Ok(hello) = application::get_app_hello(name) {
+        print_version(hello);
+    }
+
+    if let Ok(Some(health)) = application::get_app_health(name) {
+        print_health(health);
+    }
+
+    if status.schedules.is_some() || status.scheduled_restart.is_some() {
+        print_schedules(&status.schedules, &status.scheduled_restart);
+    }
+
+    print_environment(&status.env, status.clean_env, &status.path);
+
+    println!();
+
+    util::print_title_cyan("Subprocess information");
+    util::println_field_white("Full command line", status.cmd.join(" "));
+
+    Ok(())
+}
+
+fn print_last_exit(name: &str) {
+    let runs = history::get_app_history(&name.to_string()).unwrap_or_default();
+
+    let exit = match runs.into_iter().rev().find_map(|run| run.exit) {
+        Some(exit) => exit,
+        None => return,
+    };
+
+    let mut last_exit = format!(
+        "{} at {}, after {}",
+        exit.status(),
+        util::format_timestamp(exit.timestamp),
+        util::get_uptime_from_seconds(exit.runtime)
+    );
+
+    if let Some(stopped_by) = exit.stopped_by {
+        last_exit += &format!(", stopped by '{stopped_by}'");
+    }
+
+    util::println_field_white("Last exit", last_exit);
+}
+
+fn print_version(hello: Hello) {
+    if hello.is_current() {
+        util::println_field_white("crescent version", hello.version);
+        return;
+    }
+
+    let current = Hello::current();
+    let version = match hello.version.as_str() {
+        "unknown" => String::from("older version"),
+        version => format!("{version} (protocol {})", hello.protocol),
+    };
+
+    util::println_field_white(
+        "crescent version",
+        format!(
+            "{version}, restart to update to {} (protocol {})",
+            current.version, current.protocol
+        )
+        .yellow(),
+    );
+}
+
+fn print_schedules(
+    schedules: &Option<BTreeMap<String, String>>,
+    scheduled_restart: &Option<ScheduledRestart>,
+) {
+    println!();
+
+    util::print_title_cyan("Schedules");
+
+    let now = Local::now();
+
+    let next_run = |expression: &str| match schedule::next_run(expression, &now) {
+        Ok(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        Err(err) => err.to_string(),
+    };
+
+    for (expression, command) in schedules.iter().flatten() {
+        util::println_field_white(
+            expression,
+            format!("'{}', next run at {}", command.trim(), next_run(expression)),
+        );
+    }
+
+    if let Some(scheduled_restart) = scheduled_restart {
+        util::println_field_white(
+            &scheduled_restart.cron,
+            format!("restart, next run at {}", next_run(&scheduled_restart.cron)),
+        );
+    }
+}
+
+// Values of variables that look like secrets are masked.
+fn print_environment(
+    env: &Option<BTreeMap<String, String>>,
+    clean_env: bool,
+    path: &Option<String>,
+) {
+    if env.is_none() && !clean_env && path.is_none() {
+        return;
+    }
+
+    println!();
+
+    util::print_title_cyan("Environment");
+
+    if clean_env {
+        util::println_field_white("Clean", "Not inheriting the supervisor environment");
+    }
+
+    if let Some(path) = path {
+        util::println_field_white("PATH", path);
+    }
+
+    for (key, value) in env.iter().flatten() {
+        util::println_field_white(key, environment::mask(key, value));
+    }
+}
+
+fn print_health(health: HealthStatus) {
+    util::println_field_white("Health", &health);
+
+    if let Some(last_check) = health.last_check {
+        util::println_field_white("Last health check", util::format_timestamp(last_check));
+    }
+
+    if let Some(last_error) = health.last_error {
+        util::println_field_white("Health check error", last_error);
+    }
+}
+
+fn print_failed_state(name: &str, failed: FailedState) {
+    util::print_title_cyan("Application information");
+    util::println_field_white("Name", name);
+    util::println_field_white("Status", "Failed".red());
+    util::println_field_white("Failed at", util::format_timestamp(failed.failed_at));
+    util::println_field_white("Reason", failed.reason);
+    print_last_exit(name);
+
+    if failed.last_lines.is_empty() {
+        return;
+    }
+
+    println!();
+
+    util::print_title_cyan("Last log lines");
+
+    for line in failed.last_lines {
+        println!("{line}");
+    }
+}
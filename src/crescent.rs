@@ -1,14 +1,23 @@
 use std::{
+    collections::BTreeMap,
     env,
     fs::{self, File},
     io::BufReader,
-    path::PathBuf,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    access::AccessControl,
+    application::RestartPolicy,
+    error::{Error, Result},
+    health::{HealthCheck, Readiness},
+    remote::RemoteListener,
+    schedule::ScheduledRestart,
 };
 
-use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Profile {
     // Not used
     pub __comment: Option<String>,
@@ -20,14 +29,74 @@ pub struct Profile {
     pub interpreter_arguments: Option<Vec<String>>,
     pub application_arguments: Option<Vec<String>>,
     pub stop_command: Option<String>,
+    pub stop_timeout: Option<u64>,
+    pub restart_policy: Option<RestartPolicy>,
+    pub restart_delay: Option<u64>,
+    pub max_restarts: Option<u32>,
+    pub crash_loop_limit: Option<u32>,
+    pub crash_loop_window: Option<u64>,
+    pub health_check: Option<HealthCheck>,
+    pub readiness: Option<Readiness>,
+    // Cron expressions mapped to commands written to stdin.
+    pub schedules: Option<BTreeMap<String, String>>,
+    pub scheduled_restart: Option<ScheduledRestart>,
+    pub remote: Option<RemoteListener>,
+    // Users and groups allowed to use the application socket.
+    pub access: Option<AccessControl>,
+    // Applications started before this one and stopped after it.
+    pub depends_on: Option<Vec<String>>,
+    // Used to select the application in bulk commands with `--label`.
+    pub labels: Option<Vec<String>>,
+    // Environment variables, overwriting the ones from the env file.
+    pub env: Option<BTreeMap<String, String>>,
+    // Dotenv file read when the application is started.
+    pub env_file: Option<String>,
+    // Starts the subprocess without the environment of the supervisor.
+    pub clean_env: Option<bool>,
+    // Replaces PATH, the interpreter or executable is also found with it.
+    pub path: Option<String>,
+}
+
+// A group of applications managed together, usually kept next to the application files.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Stack {
+    // Not used
+    pub __comment: Option<String>,
+    // Not used
+    pub __version: Option<i8>,
+    pub apps: Vec<StackApp>,
+}
+
+// Fields set here overwrite the ones from the profile, relative file paths
+// are relative to the stack file.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StackApp {
+    pub name: String,
+    pub profile: Option<String>,
+    pub file_path: Option<String>,
+    pub interpreter: Option<String>,
+    pub interpreter_arguments: Option<Vec<String>>,
+    pub application_arguments: Option<Vec<String>>,
+    pub restart_policy: Option<RestartPolicy>,
+    pub restart_delay: Option<u64>,
+    pub max_restarts: Option<u32>,
+    pub crash_loop_limit: Option<u32>,
+    pub crash_loop_window: Option<u64>,
+    pub depends_on: Option<Vec<String>>,
+    pub labels: Option<Vec<String>>,
+    pub env: Option<BTreeMap<String, String>>,
+    pub env_file: Option<String>,
+    pub clean_env: Option<bool>,
+    pub path: Option<String>,
 }
 
 pub fn crescent_dir() -> Result<PathBuf> {
-    let home = env::var("HOME").context("Error getting HOME env.")?;
+    let home = env::var("HOME").map_err(|_| Error::HomeNotSet)?;
     let mut crescent_dir = PathBuf::from(home);
     crescent_dir.push(".crescent");
     if !crescent_dir.exists() {
-        fs::create_dir_all(&crescent_dir).context("Error creating crescent directory.")?;
+        fs::create_dir_all(&crescent_dir)
+            .map_err(Error::io("Error creating crescent directory."))?;
     }
     Ok(crescent_dir)
 }
@@ -36,16 +105,32 @@ pub fn get_apps_dir() -> Result<PathBuf> {
     let mut apps_dir = crescent_dir()?;
     apps_dir.push("apps");
     if !apps_dir.exists() {
-        fs::create_dir_all(&apps_dir).context("Error creating apps directory.")?;
+        fs::create_dir_all(&apps_dir).map_err(Error::io("Error creating apps directory."))?;
     }
     Ok(apps_dir)
 }
 
+pub fn get_app_names() -> Result<Vec<String>> {
+    let apps_dir = get_apps_dir()?;
+
+    let mut names: Vec<String> = apps_dir
+        .read_dir()
+        .map_err(Error::io("Error reading apps directory."))?
+        .flatten()
+        .filter_map(|app_dir| app_dir.file_name().to_str().map(String::from))
+        .collect();
+
+    names.sort();
+
+    Ok(names)
+}
+
 pub fn get_profiles_dir() -> Result<PathBuf> {
     let mut profiles_dir = crescent_dir()?;
     profiles_dir.push("profiles");
     if !profiles_dir.exists() {
-        fs::create_dir_all(&profiles_dir).context("Error creating profiles directory.")?;
+        fs::create_dir_all(&profiles_dir)
+            .map_err(Error::io("Error creating profiles directory."))?;
     }
     Ok(profiles_dir)
 }
@@ -55,17 +140,53 @@ pub fn get_profile(profile: &str) -> Result<Profile> {
     profiles_dir.push(profile.to_owned() + ".json");
 
     if !profiles_dir.exists() || !profiles_dir.is_file() {
-        return Err(anyhow!("Profile not found."));
+        return Err(Error::ProfileNotFound);
+    }
+
+    let file = File::open(profiles_dir).map_err(|err| Error::Io {
+        message: format!("Error opening profile file: {err}"),
+        source: err,
+    })?;
+
+    let reader = BufReader::new(file);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+pub fn get_stacks_dir() -> Result<PathBuf> {
+    let mut stacks_dir = crescent_dir()?;
+    stacks_dir.push("stacks");
+    if !stacks_dir.exists() {
+        fs::create_dir_all(&stacks_dir).map_err(Error::io("Error creating stacks directory."))?;
+    }
+    Ok(stacks_dir)
+}
+
+// Accepts a path to a stack file or the name of one in the stacks directory.
+pub fn get_stack_path(stack: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(stack);
+
+    if path.is_file() {
+        return Ok(path);
     }
 
-    match File::open(profiles_dir) {
-        Ok(file) => {
-            let reader = BufReader::new(file);
-            let profile: Profile = serde_json::from_reader(reader)?;
-            Ok(profile)
-        }
-        Err(err) => Err(anyhow!("Error opening profile file: {err}")),
+    let mut stacks_dir = get_stacks_dir()?;
+    stacks_dir.push(stack.to_owned() + ".json");
+
+    if !stacks_dir.is_file() {
+        return Err(Error::StackNotFound);
     }
+
+    Ok(stacks_dir)
+}
+
+pub fn get_stack(path: &Path) -> Result<Stack> {
+    let file = File::open(path).map_err(|err| Error::Io {
+        message: format!("Error opening stack file: {err}"),
+        source: err,
+    })?;
+
+    let reader = BufReader::new(file);
+    Ok(serde_json::from_reader(reader)?)
 }
 
 #[cfg(test)]
@@ -74,7 +195,7 @@ mod tests {
     use std::path::PathBuf;
 
     #[test]
-    fn unit_crescent_dir() -> Result<()> {
+    fn unit_crescent_dir() -> anyhow::Result<()> {
         let home = env::var("HOME")?;
         let mut home_path = PathBuf::from(home);
         home_path.push(".crescent");
@@ -98,4 +219,24 @@ mod tests {
         assert_eq!(format!("{}", err), "Profile not found.");
         Ok(())
     }
+
+    #[test]
+    fn unit_get_stack() -> anyhow::Result<()> {
+        let path = env::temp_dir().join("unit_get_stack.json");
+        fs::write(
+            &path,
+            r#"{"apps": [{"name": "lobby", "profile": "example"}, {"name": "proxy", "profile": "velocity", "depends_on": ["lobby"]}]}"#,
+        )?;
+
+        let stack = get_stack(&get_stack_path(path.to_str().unwrap())?)?;
+        assert_eq!(stack.apps.len(), 2);
+        assert_eq!(stack.apps[0].profile, Some(String::from("example")));
+        assert_eq!(stack.apps[1].depends_on, Some(vec![String::from("lobby")]));
+
+        let err = get_stack_path("unit_get_stack_does_not_exist").unwrap_err();
+        assert_eq!(format!("{}", err), "Stack not found.");
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,230 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File, Permissions},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    process,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    application::{self, Application},
+    crescent, dependencies,
+    error::Error,
+    output::{self, Sink},
+    socket, subprocess,
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+// Requests to the daemon control socket and their answers, one request per connection.
+#[derive(Serialize, Deserialize)]
+pub enum DaemonEvent {
+    // Supervises the application, its directory is reset by the client.
+    Start(Box<Application>),
+    List,
+    Apps(Vec<String>),
+    // Stops every application and then the daemon.
+    Shutdown,
+    Ack,
+    Error(String),
+}
+
+// A client not sending its request in time is disconnected, requests are handled one at a time.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Time given to the supervisor threads to clean up after their subprocess stopped.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Names of the applications supervised by the daemon.
+type Apps = Arc<Mutex<BTreeSet<String>>>;
+
+// The socket, PID file and log of the daemon are kept in the crescent directory.
+pub fn daemon_path(extension: &str) -> Result<PathBuf> {
+    Ok(crescent::crescent_dir()?.join(format!("daemon.{extension}")))
+}
+
+// Sends a request to the daemon, returns None if it is not running.
+pub fn request(event: DaemonEvent) -> Result<Option<DaemonEvent>> {
+    let mut stream = match UnixStream::connect(daemon_path("sock")?) {
+        Ok(stream) => stream,
+        Err(_) => return Ok(None),
+    };
+
+    socket::write_event(&mut stream, &event)?;
+
+    match socket::read_event(&mut stream)? {
+        Some(DaemonEvent::Error(err)) => Err(anyhow!("{err}")),
+        Some(event) => Ok(Some(event)),
+        None => Err(anyhow!(
+            "Connection to the daemon closed before a response."
+        )),
+    }
+}
+
+// Supervises the applications started through the control socket until a `Shutdown`.
+pub fn run() -> Result<()> {
+    let socket_path = daemon_path("sock")?;
+
+    // A daemon that was killed leaves its socket behind.
+    if socket_path.exists() {
+        if UnixStream::connect(&socket_path).is_ok() {
+            return Err(anyhow!("The daemon is already running."));
+        }
+
+        fs::remove_file(&socket_path).context("Error removing stale daemon socket.")?;
+    }
+
+    let listener = UnixListener::bind(&socket_path).context("Error binding daemon socket.")?;
+    fs::set_permissions(&socket_path, Permissions::from_mode(0o600))?;
+
+    info!("Daemon listening on '{}'.", socket_path.display());
+
+    let apps: Apps = Arc::new(Mutex::new(BTreeSet::new()));
+
+    for client in listener.incoming() {
+        let mut stream = match client {
+            Ok(stream) => stream,
+            Err(err) => {
+                error!("Socket error: {err}");
+                continue;
+            }
+        };
+
+        if let Err(err) = stream.set_read_timeout(Some(CLIENT_TIMEOUT)) {
+            error!("{err}");
+            continue;
+        }
+
+        let event = match socket::read_event::<_, DaemonEvent>(&mut stream) {
+            Ok(Some(event)) => event,
+            Ok(None) => continue,
+            Err(err) => {
+                error!("Error reading request: {err}");
+                continue;
+            }
+        };
+
+        let shutdown = matches!(event, DaemonEvent::Shutdown);
+
+        let response = match handle_event(event, &apps) {
+            Ok(response) => response,
+            Err(err) => DaemonEvent::Error(err.to_string()),
+        };
+
+        if let Err(err) = socket::write_event(&mut stream, &response) {
+            error!("Error sending response: {err}");
+        }
+
+        if shutdown {
+            break;
+        }
+    }
+
+    info!("Removing socket.");
+    fs::remove_file(socket_path)?;
+    let _ = fs::remove_file(daemon_path("pid")?);
+
+    info!("Shutting down.");
+    Ok(())
+}
+
+fn handle_event(event: DaemonEvent, apps: &Apps) -> Result<DaemonEvent> {
+    match event {
+        DaemonEvent::Start(app_info) => {
+            start_app(*app_info, apps)?;
+            Ok(DaemonEvent::Ack)
+        }
+        DaemonEvent::List => {
+            let names = apps.lock().unwrap().iter().cloned().collect();
+            Ok(DaemonEvent::Apps(names))
+        }
+        DaemonEvent::Shutdown => {
+            stop_apps(apps);
+            Ok(DaemonEvent::Ack)
+        }
+        _ => Err(anyhow!("Unexpected request.")),
+    }
+}
+
+// Runs the same supervisor as `start`, from a thread writing to the application log.
+fn start_app(app_info: Application, apps: &Apps) -> Result<()> {
+    let name = app_info.name.clone();
+
+    if apps.lock().unwrap().contains(&name) || application::app_already_running(&name)? {
+        return Err(Error::AlreadyRunning.into());
+    }
+
+    let app_dir = application::app_dir_by_name(&name)?;
+    fs::create_dir_all(&app_dir).context("Error creating application directory.")?;
+
+    let log = File::create(app_dir.join(name.clone() + ".log"))?;
+
+    // Written by `Daemonize` otherwise, the first line is the supervisor PID.
+    fs::write(
+        app_dir.join(name.clone() + ".pid"),
+        process::id().to_string(),
+    )?;
+
+    let sink = Arc::new(Sink::new(log));
+
+    apps.lock().unwrap().insert(name.clone());
+    let supervised = apps.clone();
+    let thread_name = name.clone();
+
+    let result = thread::Builder::new()
+        .name(format!("app_{name}"))
+        .spawn(move || {
+            output::set_sink(sink);
+
+            if let Err(err) = subprocess::start(app_info, app_dir) {
+                error!("{err}");
+            }
+
+            output::close_subscribers();
+
+            supervised.lock().unwrap().remove(&thread_name);
+        });
+
+    if let Err(err) = result {
+        apps.lock().unwrap().remove(&name);
+        return Err(anyhow!("Error starting supervisor thread: {err}"));
+    }
+
+    info!("Supervising '{name}'.");
+    Ok(())
+}
+
+// Stops every application, dependents first, waiting for their supervisor threads to finish.
+fn stop_apps(apps: &Apps) {
+    let names: Vec<String> = apps.lock().unwrap().iter().cloned().collect();
+    let running = dependencies::running_dependencies(names.clone());
+
+    let names = match dependencies::stop_order(&running) {
+        Ok(order) => order,
+        Err(err) => {
+            error!("{err}");
+            names
+        }
+    };
+
+    for name in &names {
+        match application::stop_app(name, false, None) {
+            Ok(step) => info!("'{name}': {step}"),
+            Err(err) => error!("Error stopping '{name}': {err}"),
+        }
+    }
+
+    let start = Instant::now();
+
+    while !apps.lock().unwrap().is_empty() && start.elapsed() < SHUTDOWN_TIMEOUT {
+        thread::sleep(Duration::from_millis(100));
+    }
+}
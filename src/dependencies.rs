@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use crate::application;
+
+use anyhow::{anyhow, Result};
+
+// Orders the applications so each one comes after its dependencies, keeping the given order otherwise.
+// Dependencies outside the list are expected to be running already.
+pub fn start_order(apps: &[(String, Vec<String>)]) -> Result<Vec<String>> {
+    let dependencies: HashMap<&str, &Vec<String>> = apps
+        .iter()
+        .map(|(name, dependencies)| (name.as_str(), dependencies))
+        .collect();
+
+    let mut order = vec![];
+    let mut path = vec![];
+
+    for (name, _) in apps {
+        visit(name, &dependencies, &mut path, &mut order)?;
+    }
+
+    Ok(order)
+}
+
+// Dependents are stopped before the applications they depend on.
+pub fn stop_order(apps: &[(String, Vec<String>)]) -> Result<Vec<String>> {
+    let mut order = start_order(apps)?;
+    order.reverse();
+    Ok(order)
+}
+
+// Returns an error for the first dependency that is not in the list and not running.
+pub fn check_missing(apps: &[(String, Vec<String>)]) -> Result<()> {
+    for (name, dependencies) in apps {
+        for dependency in dependencies {
+            if apps.iter().any(|(other, _)| other == dependency) {
+                continue;
+            }
+
+            if !application::app_already_running(dependency)? {
+                return Err(anyhow!(
+                    "'{name}' depends on '{dependency}', which is not running."
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Dependencies of running applications, as sent by their supervisors.
+pub fn running_dependencies(names: Vec<String>) -> Vec<(String, Vec<String>)> {
+    names
+        .into_iter()
+        .map(|name| {
+            let dependencies = application::get_app_info(&name)
+                .ok()
+                .and_then(|app_info| app_info.depends_on)
+                .unwrap_or_default();
+
+            (name, dependencies)
+        })
+        .collect()
+}
+
+fn visit<'a>(
+    name: &'a str,
+    dependencies: &HashMap<&'a str, &'a Vec<String>>,
+    path: &mut Vec<&'a str>,
+    order: &mut Vec<String>,
+) -> Result<()> {
+    if order.iter().any(|ordered| ordered == name) {
+        return Ok(());
+    }
+
+    if let Some(start) = path.iter().position(|visiting| *visiting == name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(name);
+        return Err(anyhow!("Dependency cycle: {}.", cycle.join(" -> ")));
+    }
+
+    let app_dependencies = match dependencies.get(name) {
+        Some(app_dependencies) => app_dependencies,
+        None => return Ok(()),
+    };
+
+    path.push(name);
+
+    for dependency in app_dependencies.iter() {
+        visit(dependency, dependencies, path, order)?;
+    }
+
+    path.pop();
+    order.push(name.to_string());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(name: &str, dependencies: &[&str]) -> (String, Vec<String>) {
+        let dependencies = dependencies.iter().map(|name| name.to_string()).collect();
+        (name.to_string(), dependencies)
+    }
+
+    #[test]
+    fn unit_dependencies_order() -> Result<()> {
+        let apps = [
+            app("velocity", &["lobby", "survival"]),
+            app("lobby", &["database"]),
+            app("survival", &["database"]),
+            app("database", &[]),
+            app("other", &[]),
+        ];
+
+        assert_eq!(
+            start_order(&apps)?,
+            ["database", "lobby", "survival", "velocity", "other"]
+        );
+        assert_eq!(
+            stop_order(&apps)?,
+            ["other", "velocity", "survival", "lobby", "database"]
+        );
+
+        // Dependencies that are not being started are ignored.
+        let apps = [app("velocity", &["lobby"])];
+        assert_eq!(start_order(&apps)?, ["velocity"]);
+        Ok(())
+    }
+
+    #[test]
+    fn unit_dependencies_cycle() {
+        let apps = [
+            app("velocity", &["lobby"]),
+            app("lobby", &["survival"]),
+            app("survival", &["velocity"]),
+        ];
+
+        let err = start_order(&apps).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Dependency cycle: velocity -> lobby -> survival -> velocity."
+        );
+
+        let apps = [app("lobby", &["lobby"])];
+        let err = stop_order(&apps).unwrap_err();
+        assert_eq!(format!("{}", err), "Dependency cycle: lobby -> lobby.");
+    }
+
+    #[test]
+    fn unit_dependencies_check_missing() -> Result<()> {
+        let apps = [app("velocity", &["lobby"]), app("lobby", &[])];
+        check_missing(&apps)?;
+
+        let apps = [app("velocity", &["unit_dependencies_not_running"])];
+        let err = check_missing(&apps).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "'velocity' depends on 'unit_dependencies_not_running', which is not running."
+        );
+        Ok(())
+    }
+}
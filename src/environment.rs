@@ -0,0 +1,399 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs,
+    io::Read,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::application::Application;
+
+use anyhow::{anyhow, Context, Result};
+
+// Shown instead of the values of variables that look like secrets.
+const MASK: &str = "********";
+
+// Parts of a variable name that mark its value as a secret.
+const SECRET_PARTS: [&str; 8] = [
+    "SECRET",
+    "TOKEN",
+    "PASSWORD",
+    "PASSWD",
+    "KEY",
+    "AUTH",
+    "CREDENTIAL",
+    "PRIVATE",
+];
+
+// Variables from the env file are overwritten by the profile `env`, and those by `--env`.
+pub fn resolve(
+    env_file: Option<&str>,
+    profile_env: Option<&BTreeMap<String, String>>,
+    assignments: Option<&[String]>,
+) -> Result<Option<BTreeMap<String, String>>> {
+    let mut vars = match env_file {
+        Some(path) => read_env_file(Path::new(path))?,
+        None => BTreeMap::new(),
+    };
+
+    for (key, value) in profile_env.into_iter().flatten() {
+        validate_key(key)?;
+        vars.insert(key.clone(), value.clone());
+    }
+
+    for assignment in assignments.into_iter().flatten() {
+        let (key, value) = parse_assignment(assignment)?;
+        vars.insert(key, value);
+    }
+
+    match vars.is_empty() {
+        true => Ok(None),
+        false => Ok(Some(vars)),
+    }
+}
+
+// Removes the variables with the same value as in the env file, saved applications
+// read it again when started instead of keeping its secrets in the save file.
+pub fn without_env_file(
+    env: Option<BTreeMap<String, String>>,
+    env_file: &str,
+) -> Result<Option<BTreeMap<String, String>>> {
+    let file_vars = read_env_file(Path::new(env_file))?;
+
+    let vars: BTreeMap<String, String> = env
+        .into_iter()
+        .flatten()
+        .filter(|(key, value)| file_vars.get(key) != Some(value))
+        .collect();
+
+    match vars.is_empty() {
+        true => Ok(None),
+        false => Ok(Some(vars)),
+    }
+}
+
+// `KEY=VALUE`, the value can be empty.
+pub fn parse_assignment(assignment: &str) -> Result<(String, String)> {
+    let (key, value) = assignment
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid variable '{assignment}', expected 'KEY=VALUE'."))?;
+
+    validate_key(key)?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+pub fn read_env_file(path: &Path) -> Result<BTreeMap<String, String>> {
+    let content = fs::read_to_string(path)
+        .context(format!("Error reading env file '{}'.", path.display()))?;
+
+    parse_env_file(&content)
+        .map_err(|err| anyhow!("Error parsing env file '{}': {err}", path.display()))
+}
+
+// Dotenv syntax: `KEY=VALUE` lines with an optional `export`, comments starting with `#`,
+// values in single quotes kept as is and values in double quotes with `\n`, `\"` and `\\`
+// escapes, which can span several lines.
+pub fn parse_env_file(content: &str) -> Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((i, line)) = lines.next() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {} is not 'KEY=VALUE'.", i + 1))?;
+
+        let key = key.trim();
+        validate_key(key).map_err(|err| anyhow!("line {}: {err}", i + 1))?;
+
+        let value = value.trim_start();
+
+        let value = if let Some(quoted) = value.strip_prefix('"') {
+            let mut quoted = quoted.to_string();
+
+            // The value continues on the next lines until the closing quote.
+            while find_closing_quote(&quoted).is_none() {
+                match lines.next() {
+                    Some((_, next)) => {
+                        quoted.push('\n');
+                        quoted.push_str(next);
+                    }
+                    None => return Err(anyhow!("line {} has an unclosed quote.", i + 1)),
+                }
+            }
+
+            let end = find_closing_quote(&quoted).unwrap();
+            unescape(&quoted[..end])
+        } else if let Some(quoted) = value.strip_prefix('\'') {
+            match quoted.find('\'') {
+                Some(end) => quoted[..end].to_string(),
+                None => return Err(anyhow!("line {} has an unclosed quote.", i + 1)),
+            }
+        } else {
+            // Comments after unquoted values need a space before the `#`.
+            let value = match value.find(" #") {
+                Some(start) => &value[..start],
+                None => value,
+            };
+
+            value.trim_end().to_string()
+        };
+
+        vars.insert(key.to_string(), value);
+    }
+
+    Ok(vars)
+}
+
+// Reads the variables sent as JSON by the process that resolved them.
+pub fn read_vars<R: Read>(reader: R) -> Result<BTreeMap<String, String>> {
+    serde_json::from_reader(reader).context("Error reading environment variables.")
+}
+
+// Environment of the subprocess, None keeps the supervisor environment as is.
+pub fn subprocess_env(app_info: &Application) -> Option<Vec<(OsString, OsString)>> {
+    if app_info.env.is_none() && !app_info.clean_env && app_info.path.is_none() {
+        return None;
+    }
+
+    let mut vars: Vec<(OsString, OsString)> = match app_info.clean_env {
+        true => vec![],
+        false => env::vars_os().collect(),
+    };
+
+    for (key, value) in app_info.env.iter().flatten() {
+        vars.push((key.into(), value.into()));
+    }
+
+    if let Some(path) = &app_info.path {
+        vars.push(("PATH".into(), path.into()));
+    }
+
+    Some(vars)
+}
+
+// The executable is otherwise looked up with the PATH of the supervisor.
+pub fn find_executable(program: &str, path: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        return None;
+    }
+
+    path.split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(dir).join(program))
+        .find(|candidate| match fs::metadata(candidate) {
+            Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+            Err(_) => false,
+        })
+}
+
+pub fn is_secret(key: &str) -> bool {
+    let key = key.to_uppercase();
+    SECRET_PARTS.iter().any(|part| key.contains(part))
+}
+
+pub fn mask(key: &str, value: &str) -> String {
+    match is_secret(key) {
+        true => String::from(MASK),
+        false => value.to_string(),
+    }
+}
+
+// Masks the values that look like secrets, for the application information shown to users.
+pub fn mask_application(app_info: &mut Application) {
+    for (key, value) in app_info.env.iter_mut().flatten() {
+        *value = mask(key, value);
+    }
+
+    let cli_env = app_info.cli_args.iter_mut().flat_map(|args| &mut args.env);
+
+    for assignment in app_info.start_args.env.iter_mut().chain(cli_env).flatten() {
+        if let Some((key, value)) = assignment.split_once('=') {
+            *assignment = format!("{key}={}", mask(key, value));
+        }
+    }
+}
+
+fn validate_key(key: &str) -> Result<()> {
+    let valid = key
+        .chars()
+        .enumerate()
+        .all(|(i, c)| c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()));
+
+    if key.is_empty() || !valid {
+        return Err(anyhow!("Invalid variable name '{key}'."));
+    }
+
+    Ok(())
+}
+
+fn find_closing_quote(value: &str) -> Option<usize> {
+    let mut escaped = false;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+
+    None
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_environment_parse_env_file() -> Result<()> {
+        let content = r#"
+# Database
+export DB_HOST=localhost
+DB_PORT = 5432 # default port
+DB_PASSWORD='p#ss "word"'
+MOTD="Welcome\n\"player\""
+CERT="line one
+line two"
+EMPTY=
+"#;
+
+        let vars = parse_env_file(content)?;
+        assert_eq!(vars["DB_HOST"], "localhost");
+        assert_eq!(vars["DB_PORT"], "5432");
+        assert_eq!(vars["DB_PASSWORD"], "p#ss \"word\"");
+        assert_eq!(vars["MOTD"], "Welcome\n\"player\"");
+        assert_eq!(vars["CERT"], "line one\nline two");
+        assert_eq!(vars["EMPTY"], "");
+        assert_eq!(vars.len(), 6);
+
+        let err = parse_env_file("DB_HOST=localhost\nDB_PORT").unwrap_err();
+        assert_eq!(format!("{}", err), "line 2 is not 'KEY=VALUE'.");
+
+        let err = parse_env_file("1DB=localhost").unwrap_err();
+        assert_eq!(format!("{}", err), "line 1: Invalid variable name '1DB'.");
+
+        let err = parse_env_file("MOTD=\"Welcome").unwrap_err();
+        assert_eq!(format!("{}", err), "line 1 has an unclosed quote.");
+        Ok(())
+    }
+
+    #[test]
+    fn unit_environment_resolve() -> Result<()> {
+        let path = env::temp_dir().join("unit_environment_resolve.env");
+        fs::write(&path, "LEVEL=world\nMODE=survival\nDIFFICULTY=easy\n")?;
+
+        let profile_env = BTreeMap::from([
+            (String::from("MODE"), String::from("creative")),
+            (String::from("DIFFICULTY"), String::from("normal")),
+        ]);
+
+        let vars = resolve(
+            path.to_str(),
+            Some(&profile_env),
+            Some(&[String::from("DIFFICULTY=hard")]),
+        )?
+        .unwrap();
+
+        assert_eq!(vars["LEVEL"], "world");
+        assert_eq!(vars["MODE"], "creative");
+        assert_eq!(vars["DIFFICULTY"], "hard");
+
+        assert!(resolve(None, None, None)?.is_none());
+
+        let saved = without_env_file(Some(vars), path.to_str().unwrap())?.unwrap();
+        assert_eq!(saved.len(), 2);
+        assert!(!saved.contains_key("LEVEL"));
+
+        let vars = resolve(path.to_str(), Some(&saved), None)?.unwrap();
+        assert_eq!(vars["LEVEL"], "world");
+        assert_eq!(vars["DIFFICULTY"], "hard");
+
+        let err = resolve(None, None, Some(&[String::from("DIFFICULTY")])).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Invalid variable 'DIFFICULTY', expected 'KEY=VALUE'."
+        );
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unit_environment_subprocess_env() {
+        let mut app_info = Application::default();
+        assert!(subprocess_env(&app_info).is_none());
+
+        app_info.env = Some(BTreeMap::from([(
+            String::from("LEVEL"),
+            String::from("world"),
+        )]));
+        app_info.clean_env = true;
+        app_info.path = Some(String::from("/opt/java/bin"));
+
+        let vars = subprocess_env(&app_info).unwrap();
+        assert_eq!(
+            vars,
+            [
+                (OsString::from("LEVEL"), OsString::from("world")),
+                (OsString::from("PATH"), OsString::from("/opt/java/bin")),
+            ]
+        );
+
+        assert!(find_executable("sh", "/does-not-exist:/bin:/usr/bin").is_some());
+        assert!(find_executable("./sh", "/bin").is_none());
+    }
+
+    #[test]
+    fn unit_environment_mask() {
+        assert!(is_secret("RCON_PASSWORD"));
+        assert!(is_secret("api_key"));
+        assert!(!is_secret("LEVEL"));
+
+        let mut app_info = Application {
+            env: Some(BTreeMap::from([
+                (String::from("DB_TOKEN"), String::from("hunter2")),
+                (String::from("LEVEL"), String::from("world")),
+            ])),
+            ..Default::default()
+        };
+        app_info.start_args.env = Some(vec![String::from("DB_TOKEN=hunter2")]);
+
+        mask_application(&mut app_info);
+
+        let env = app_info.env.unwrap();
+        assert_eq!(env["DB_TOKEN"], MASK);
+        assert_eq!(env["LEVEL"], "world");
+        assert_eq!(app_info.start_args.env.unwrap(), ["DB_TOKEN=********"]);
+    }
+}
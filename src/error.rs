@@ -0,0 +1,140 @@
+use std::{fmt::Display, io};
+
+// Errors from the application registry, the socket and the client.
+// The messages are the same ones printed by the commands.
+#[derive(Debug)]
+pub enum Error {
+    HomeNotSet,
+    AppNotFound,
+    AppNotRunning,
+    AlreadyRunning,
+    LogNotFound,
+    ProfileNotFound,
+    StackNotFound,
+    NoMatchingApp(String),
+    NoLabeledApp(String),
+    // A file in the application directory could not be read.
+    Corrupted(String),
+    // Could not connect to a supervisor, it is not running or left its socket behind.
+    Connection {
+        target: String,
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
+    // The supervisor was started by a version without the capability.
+    Unsupported {
+        name: String,
+        version: String,
+        capability: String,
+    },
+    // An `Error` response, with the reason from the supervisor.
+    Application(String),
+    // The connection was closed early or the response was not the expected one.
+    Protocol(String),
+    Timeout(String),
+    Io {
+        message: String,
+        source: io::Error,
+    },
+    Json(serde_json::Error),
+}
+
+impl Error {
+    // Adds what was being done when the I/O error happened.
+    pub fn io(message: impl ToString) -> impl FnOnce(io::Error) -> Error {
+        let message = message.to_string();
+        move |source| Error::Io { message, source }
+    }
+
+    pub fn unexpected_response() -> Error {
+        Error::Protocol(String::from("Unexpected response from the application."))
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::HomeNotSet => write!(f, "Error getting HOME env."),
+            Error::AppNotFound => write!(f, "Application does not exist."),
+            Error::AppNotRunning => write!(f, "Application not running."),
+            Error::AlreadyRunning => {
+                write!(f, "An application with the same name is already running.")
+            }
+            Error::LogNotFound => write!(f, "Log file does not exist."),
+            Error::ProfileNotFound => write!(f, "Profile not found."),
+            Error::StackNotFound => write!(f, "Stack not found."),
+            Error::NoMatchingApp(pattern) => {
+                write!(f, "No running application matches '{pattern}'.")
+            }
+            Error::NoLabeledApp(label) => {
+                write!(f, "No running application has the label '{label}'.")
+            }
+            Error::Connection { target, .. } => write!(f, "Error connecting to {target}."),
+            Error::Unsupported {
+                name,
+                version,
+                capability,
+            } => write!(
+                f,
+                "'{name}' was started by {version}, restart it to use '{capability}'."
+            ),
+            Error::Corrupted(err)
+            | Error::Application(err)
+            | Error::Protocol(err)
+            | Error::Timeout(err) => {
+                write!(f, "{err}")
+            }
+            Error::Io { message, .. } => write!(f, "{message}"),
+            Error::Json(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Connection {
+                source: Some(source),
+                ..
+            } => Some(source.as_ref()),
+            Error::Io { source, .. } => Some(source),
+            Error::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io {
+            message: source.to_string(),
+            source,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_error_display() {
+        let err = Error::Connection {
+            target: String::from("'app' socket"),
+            source: Some(Box::new(io::Error::from(io::ErrorKind::NotFound))),
+        };
+        assert_eq!(err.to_string(), "Error connecting to 'app' socket.");
+        assert!(std::error::Error::source(&err).is_some());
+
+        let err = anyhow::Error::from(Error::AppNotFound);
+        assert_eq!(err.to_string(), "Application does not exist.");
+        assert!(matches!(err.downcast_ref(), Some(Error::AppNotFound)));
+    }
+}
@@ -0,0 +1,510 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// Defaults used when the profile does not set them.
+pub const DEFAULT_INTERVAL: u64 = 30;
+pub const DEFAULT_TIMEOUT: u64 = 5;
+pub const DEFAULT_RETRIES: u32 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HealthCheck {
+    #[serde(flatten)]
+    pub probe: HealthProbe,
+    // Seconds between checks.
+    pub interval: Option<u64>,
+    // Seconds a single check can take before failing.
+    pub timeout: Option<u64>,
+    // Consecutive failures that mark the application as unhealthy.
+    pub retries: Option<u32>,
+    // Seconds after every start in which failures are not counted.
+    pub start_period: Option<u64>,
+    pub action: Option<HealthAction>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HealthProbe {
+    // Connects to a TCP port, host defaults to 127.0.0.1.
+    Tcp {
+        host: Option<String>,
+        port: u16,
+    },
+    // Runs a command and compares its exit code, defaults to 0.
+    Command {
+        command: Vec<String>,
+        expected_code: Option<i32>,
+    },
+    // Writes a command to the subprocess stdin and waits for a line in the log matching `expect`.
+    Stdin {
+        command: String,
+        expect: String,
+    },
+}
+
+// What the supervisor does once the application is unhealthy.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthAction {
+    #[default]
+    None,
+    Restart,
+    Stop,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Health {
+    #[default]
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HealthStatus {
+    pub health: Health,
+    // Consecutive failures, reset after a successful check.
+    pub failures: u32,
+    // Unix timestamp in seconds.
+    pub last_check: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+// Condition that marks the application as ready after starting.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Readiness {
+    // A line in the log matching the regex, such as 'Done \(.*\)! For help'.
+    LogLine { regex: String },
+    Tcp { host: Option<String>, port: u16 },
+    // A file appearing, relative paths start from the application directory.
+    File { path: String },
+}
+
+impl HealthCheck {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.interval == Some(0) {
+            return Err(anyhow!("Health check interval must be greater than 0."));
+        }
+
+        match &self.probe {
+            HealthProbe::Command { command, .. } if command.is_empty() => {
+                Err(anyhow!("Health check command is empty."))
+            }
+            HealthProbe::Stdin { expect, .. } => {
+                Regex::new(expect).context("Invalid health check regex.")?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval.unwrap_or(DEFAULT_INTERVAL))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT))
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries.unwrap_or(DEFAULT_RETRIES).max(1)
+    }
+
+    pub fn start_period(&self) -> Duration {
+        Duration::from_secs(self.start_period.unwrap_or(0))
+    }
+}
+
+impl Readiness {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Readiness::LogLine { regex } = self {
+            Regex::new(regex).context("Invalid readiness regex.")?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn is_ready(&self, log_path: &Path, work_dir: &Path) -> Result<bool> {
+        match self {
+            Readiness::LogLine { regex } => {
+                let regex = Regex::new(regex).context("Invalid readiness regex.")?;
+                let mut log = String::new();
+                File::open(log_path)
+                    .context("Error opening log file.")?
+                    .read_to_string(&mut log)?;
+                Ok(log.lines().any(|line| regex.is_match(line)))
+            }
+            Readiness::Tcp { host, port } => {
+                let host = host.as_deref().unwrap_or("127.0.0.1");
+                Ok(check_tcp(host, *port, Duration::from_secs(1)).is_ok())
+            }
+            Readiness::File { path } => Ok(work_dir.join(PathBuf::from(path)).exists()),
+        }
+    }
+}
+
+impl Display for Readiness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Readiness::LogLine { regex } => write!(f, "log line matching '{regex}'"),
+            Readiness::Tcp { host, port } => {
+                write!(f, "tcp {}:{port}", host.as_deref().unwrap_or("127.0.0.1"))
+            }
+            Readiness::File { path } => write!(f, "file '{path}'"),
+        }
+    }
+}
+
+impl Display for HealthCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, every {}s, unhealthy after {} failures",
+            self.probe,
+            self.interval().as_secs(),
+            self.retries()
+        )?;
+
+        match self.action.unwrap_or_default() {
+            HealthAction::None => Ok(()),
+            HealthAction::Restart => write!(f, ", then restart"),
+            HealthAction::Stop => write!(f, ", then stop"),
+        }
+    }
+}
+
+impl HealthProbe {
+    // `write_stdin` is only used by the stdin probe, the log is where the subprocess output goes.
+    // Commands run in the working directory of the application.
+    pub(crate) fn check<F: FnOnce(String)>(
+        &self,
+        timeout: Duration,
+        log_path: &Path,
+        work_dir: &Path,
+        write_stdin: F,
+    ) -> Result<()> {
+        match self {
+            HealthProbe::Tcp { host, port } => {
+                let host = host.as_deref().unwrap_or("127.0.0.1");
+                check_tcp(host, *port, timeout)
+            }
+            HealthProbe::Command {
+                command,
+                expected_code,
+            } => check_command(command, expected_code.unwrap_or(0), work_dir, timeout),
+            HealthProbe::Stdin { command, expect } => {
+                check_stdin(command, expect, timeout, log_path, write_stdin)
+            }
+        }
+    }
+}
+
+impl Display for HealthProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthProbe::Tcp { host, port } => {
+                write!(f, "tcp {}:{port}", host.as_deref().unwrap_or("127.0.0.1"))
+            }
+            HealthProbe::Command {
+                command,
+                expected_code,
+            } => write!(
+                f,
+                "command '{}' exiting with {}",
+                command.join(" "),
+                expected_code.unwrap_or(0)
+            ),
+            HealthProbe::Stdin { command, expect } => {
+                write!(f, "stdin '{}' expecting '{expect}'", command.trim())
+            }
+        }
+    }
+}
+
+impl HealthStatus {
+    // Returns true when the failure that made the application unhealthy was recorded.
+    pub(crate) fn record(
+        &mut self,
+        timestamp: u64,
+        result: Result<()>,
+        retries: u32,
+        in_start_period: bool,
+    ) -> bool {
+        self.last_check = Some(timestamp);
+
+        let err = match result {
+            Ok(_) => {
+                self.health = Health::Healthy;
+                self.failures = 0;
+                self.last_error = None;
+                return false;
+            }
+            Err(err) => err,
+        };
+
+        self.last_error = Some(err.to_string());
+
+        if in_start_period {
+            return false;
+        }
+
+        self.failures += 1;
+
+        if self.failures < retries {
+            return false;
+        }
+
+        self.health = Health::Unhealthy;
+        self.failures == retries
+    }
+}
+
+impl Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.health {
+            Health::Starting => write!(f, "Starting"),
+            Health::Healthy => write!(f, "Healthy"),
+            Health::Unhealthy => write!(f, "Unhealthy ({} failures)", self.failures),
+        }
+    }
+}
+
+fn check_tcp(host: &str, port: u16, timeout: Duration) -> Result<()> {
+    let address = (host, port)
+        .to_socket_addrs()
+        .context(format!("Error resolving '{host}'."))?
+        .next()
+        .context(format!("No address found for '{host}'."))?;
+
+    TcpStream::connect_timeout(&address, timeout)
+        .context(format!("Error connecting to '{address}'."))?;
+
+    Ok(())
+}
+
+fn check_command(
+    command: &[String],
+    expected_code: i32,
+    work_dir: &Path,
+    timeout: Duration,
+) -> Result<()> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .current_dir(work_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context(format!("Error running '{}'.", command[0]))?;
+
+    let start = Instant::now();
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return match status.code() {
+                Some(code) if code == expected_code => Ok(()),
+                Some(code) => Err(anyhow!("Command exited with code {code}.")),
+                None => Err(anyhow!("Command was terminated by a signal.")),
+            };
+        }
+
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("Command timed out after {}s.", timeout.as_secs()));
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn check_stdin<F: FnOnce(String)>(
+    command: &str,
+    expect: &str,
+    timeout: Duration,
+    log_path: &Path,
+    write_stdin: F,
+) -> Result<()> {
+    let regex = Regex::new(expect).context("Invalid health check regex.")?;
+
+    let mut log = File::open(log_path).context("Error opening log file.")?;
+    log.seek(SeekFrom::End(0))?;
+
+    write_stdin(command.trim().to_owned() + "\n");
+
+    let start = Instant::now();
+    let mut output = String::new();
+
+    loop {
+        log.read_to_string(&mut output)?;
+
+        if output.lines().any(|line| regex.is_match(line)) {
+            return Ok(());
+        }
+
+        if start.elapsed() >= timeout {
+            return Err(anyhow!(
+                "No output matching '{expect}' after {}s.",
+                timeout.as_secs()
+            ));
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, io::Write, net::TcpListener};
+
+    #[test]
+    fn unit_health_check_from_profile() -> Result<()> {
+        let json = r#"{"type": "stdin", "command": "list", "expect": "players online", "interval": 10, "action": "restart"}"#;
+        let check: HealthCheck = serde_json::from_str(json)?;
+
+        assert_eq!(
+            check.probe,
+            HealthProbe::Stdin {
+                command: String::from("list"),
+                expect: String::from("players online")
+            }
+        );
+        assert_eq!(check.interval(), Duration::from_secs(10));
+        assert_eq!(check.retries(), DEFAULT_RETRIES);
+        assert_eq!(check.action, Some(HealthAction::Restart));
+        check.validate()?;
+
+        let json = r#"{"type": "stdin", "command": "list", "expect": "("}"#;
+        let check: HealthCheck = serde_json::from_str(json)?;
+        let err = check.validate().unwrap_err();
+        assert_eq!(format!("{}", err), "Invalid health check regex.");
+        Ok(())
+    }
+
+    #[test]
+    fn unit_health_status_record() {
+        let mut status = HealthStatus::default();
+
+        assert!(!status.record(1, Err(anyhow!("down")), 2, true));
+        assert_eq!(status.health, Health::Starting);
+        assert_eq!(status.failures, 0);
+
+        assert!(!status.record(2, Ok(()), 2, false));
+        assert_eq!(status.health, Health::Healthy);
+
+        assert!(!status.record(3, Err(anyhow!("down")), 2, false));
+        assert_eq!(status.health, Health::Healthy);
+        assert!(status.record(4, Err(anyhow!("down")), 2, false));
+        assert_eq!(status.health, Health::Unhealthy);
+        assert_eq!(format!("{status}"), "Unhealthy (2 failures)");
+
+        // The action only runs once until the application recovers.
+        assert!(!status.record(5, Err(anyhow!("down")), 2, false));
+        assert_eq!(status.last_error, Some(String::from("down")));
+
+        assert!(!status.record(6, Ok(()), 2, false));
+        assert_eq!(status.failures, 0);
+        assert_eq!(status.last_check, Some(6));
+    }
+
+    #[test]
+    fn unit_health_probe_check() -> Result<()> {
+        let timeout = Duration::from_secs(1);
+        let log_path = Path::new("/does/not/exist");
+        let work_dir = env::temp_dir();
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+
+        let probe = HealthProbe::Tcp { host: None, port };
+        probe.check(timeout, log_path, &work_dir, |_| {})?;
+
+        drop(listener);
+        assert!(probe.check(timeout, log_path, &work_dir, |_| {}).is_err());
+
+        let probe = HealthProbe::Command {
+            command: vec![String::from("true")],
+            expected_code: None,
+        };
+        probe.check(timeout, log_path, &work_dir, |_| {})?;
+
+        let probe = HealthProbe::Command {
+            command: vec![String::from("false")],
+            expected_code: None,
+        };
+        let err = probe
+            .check(timeout, log_path, &work_dir, |_| {})
+            .unwrap_err();
+        assert_eq!(format!("{}", err), "Command exited with code 1.");
+        Ok(())
+    }
+
+    #[test]
+    fn unit_readiness_is_ready() -> Result<()> {
+        let work_dir = env::temp_dir();
+        let log_path = work_dir.join("unit_readiness_is_ready.log");
+        fs::write(&log_path, "Loading\n")?;
+
+        let json = r#"{"type": "log_line", "regex": "Done \\(.*\\)! For help"}"#;
+        let readiness: Readiness = serde_json::from_str(json)?;
+        readiness.validate()?;
+
+        assert!(!readiness.is_ready(&log_path, &work_dir)?);
+        fs::write(&log_path, "Loading\nDone (3.2s)! For help, type \"help\"\n")?;
+        assert!(readiness.is_ready(&log_path, &work_dir)?);
+
+        let readiness = Readiness::File {
+            path: String::from("unit_readiness_is_ready.log"),
+        };
+        assert!(readiness.is_ready(&log_path, &work_dir)?);
+        fs::remove_file(&log_path)?;
+        assert!(!readiness.is_ready(&log_path, &work_dir)?);
+
+        let readiness = Readiness::LogLine {
+            regex: String::from("("),
+        };
+        let err = readiness.validate().unwrap_err();
+        assert_eq!(format!("{}", err), "Invalid readiness regex.");
+        Ok(())
+    }
+
+    #[test]
+    fn unit_health_probe_check_stdin() -> Result<()> {
+        let work_dir = env::temp_dir();
+        let log_path = work_dir.join("unit_health_probe_check_stdin.log");
+        fs::write(&log_path, "pong\n")?;
+
+        let probe = HealthProbe::Stdin {
+            command: String::from("ping"),
+            expect: String::from("^pong$"),
+        };
+
+        // Only output written after the command counts.
+        let err = probe
+            .check(Duration::from_millis(200), &log_path, &work_dir, |_| {})
+            .unwrap_err();
+        assert_eq!(format!("{}", err), "No output matching '^pong$' after 0s.");
+
+        probe.check(Duration::from_secs(1), &log_path, &work_dir, |command| {
+            assert_eq!(command, "ping\n");
+            let mut log = fs::OpenOptions::new().append(true).open(&log_path).unwrap();
+            log.write_all(b"pong\n").unwrap();
+        })?;
+
+        fs::remove_file(log_path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use crate::application;
+
+use anyhow::{Context, Result};
+use log::error;
+use serde::{Deserialize, Serialize};
+use subprocess::ExitStatus;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StoppedBy {
+    Stop,
+    Restart,
+    HealthCheck,
+    ScheduledRestart,
+}
+
+impl Display for StoppedBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoppedBy::Stop => write!(f, "cres stop"),
+            StoppedBy::Restart => write!(f, "cres restart"),
+            StoppedBy::HealthCheck => write!(f, "health check"),
+            StoppedBy::ScheduledRestart => write!(f, "scheduled restart"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExitRecord {
+    // Unix timestamp in seconds.
+    pub timestamp: u64,
+    pub code: Option<u32>,
+    pub signal: Option<u8>,
+    // Seconds the subprocess was running for.
+    pub runtime: u64,
+    pub stopped_by: Option<StoppedBy>,
+}
+
+impl ExitRecord {
+    pub fn new(
+        timestamp: u64,
+        status: &ExitStatus,
+        runtime: u64,
+        stopped_by: Option<StoppedBy>,
+    ) -> ExitRecord {
+        let (code, signal) = match status {
+            ExitStatus::Exited(code) => (Some(*code), None),
+            ExitStatus::Signaled(signal) => (None, Some(*signal)),
+            _ => (None, None),
+        };
+
+        ExitRecord {
+            timestamp,
+            code,
+            signal,
+            runtime,
+            stopped_by,
+        }
+    }
+
+    pub fn status(&self) -> String {
+        match (self.code, self.signal) {
+            (Some(code), _) => format!("code {code}"),
+            (None, Some(signal)) => format!("signal {signal}"),
+            _ => String::from("unknown"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HistoryEvent {
+    Start { timestamp: u64, pid: u32 },
+    Exit(ExitRecord),
+}
+
+// A start and its exit, if the subprocess already exited.
+#[derive(Clone, Debug)]
+pub struct Run {
+    pub started_at: u64,
+    pub pid: u32,
+    pub exit: Option<ExitRecord>,
+}
+
+pub fn append_event(history_path: &Path, event: &HistoryEvent) {
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|mut file| {
+            let mut line = serde_json::to_vec(event)?;
+            line.push(b'\n');
+            Ok(file.write_all(&line)?)
+        });
+
+    if let Err(err) = result {
+        error!("Error writing to history file: {err}.");
+    }
+}
+
+pub fn get_app_history(name: &String) -> Result<Vec<Run>> {
+    let mut history_path = application::app_dir_by_name(name)?;
+    history_path.push(format!("{}.history", name));
+
+    if !history_path.exists() {
+        return Ok(vec![]);
+    }
+
+    read_runs(&history_path)
+}
+
+fn read_runs(history_path: &Path) -> Result<Vec<Run>> {
+    let file = File::open(history_path).context("Error opening history file.")?;
+
+    let mut runs: Vec<Run> = vec![];
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+
+        // Skipping lines that might have been cut short.
+        let event = match serde_json::from_str::<HistoryEvent>(&line) {
+            Ok(event) => event,
+            Err(_) => continue,
+        };
+
+        match event {
+            HistoryEvent::Start { timestamp, pid } => runs.push(Run {
+                started_at: timestamp,
+                pid,
+                exit: None,
+            }),
+            HistoryEvent::Exit(exit) => {
+                if let Some(run) = runs.last_mut() {
+                    if run.exit.is_none() {
+                        run.exit = Some(exit);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(runs)
+}
+
+// Removes every file in the application directory except the history.
+pub fn reset_app_dir(app_dir: &Path, name: &str) -> Result<()> {
+    let history_file = format!("{}.history", name);
+
+    for entry in app_dir.read_dir()?.flatten() {
+        if entry.file_name().to_str() == Some(history_file.as_str()) {
+            continue;
+        }
+
+        let path = entry.path();
+
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env::temp_dir, fs::remove_file};
+
+    #[test]
+    fn unit_history_read_runs() -> Result<()> {
+        let history_path = temp_dir().join("crescent_temp_history_test.history");
+
+        if history_path.exists() {
+            remove_file(&history_path)?;
+        }
+
+        append_event(
+            &history_path,
+            &HistoryEvent::Start {
+                timestamp: 10,
+                pid: 1,
+            },
+        );
+        append_event(
+            &history_path,
+            &HistoryEvent::Exit(ExitRecord::new(
+                20,
+                &ExitStatus::Signaled(15),
+                10,
+                Some(StoppedBy::Stop),
+            )),
+        );
+        append_event(
+            &history_path,
+            &HistoryEvent::Start {
+                timestamp: 30,
+                pid: 2,
+            },
+        );
+
+        let runs = read_runs(&history_path)?;
+        assert_eq!(runs.len(), 2);
+
+        let exit = runs[0].exit.clone().unwrap();
+        assert_eq!(exit.status(), "signal 15");
+        assert_eq!(exit.stopped_by, Some(StoppedBy::Stop));
+        assert!(runs[1].exit.is_none());
+
+        remove_file(history_path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+// The library behind the `cres` binary, to manage applications without shelling out.
+// Only the client, the socket protocol, the errors, the registry and the types they use are public,
+// the commands and the supervisor are internals of the binary.
+
+use std::process::ExitCode;
+
+mod access;
+mod application;
+mod cli;
+pub mod client;
+mod commands;
+mod crescent;
+mod daemon;
+mod dependencies;
+mod environment;
+pub mod error;
+mod health;
+mod history;
+mod logger;
+mod output;
+mod remote;
+mod schedule;
+pub mod socket;
+mod subprocess;
+mod tail;
+mod util;
+
+pub use application::Application;
+pub use client::{Client, Subscription};
+pub use crescent::Profile;
+pub use error::{Error, Result};
+pub use subprocess::{SocketEvent, StopStep};
+
+// Types of the application, profile and event fields.
+pub mod types {
+    pub use crate::access::{AccessControl, AccessLevel};
+    pub use crate::application::{FailedState, RestartPolicy};
+    pub use crate::commands::start::StartArgs;
+    pub use crate::health::{
+        Health, HealthAction, HealthCheck, HealthProbe, HealthStatus, Readiness,
+    };
+    pub use crate::history::StoppedBy;
+    pub use crate::output::Capture;
+    pub use crate::remote::RemoteListener;
+    pub use crate::schedule::ScheduledRestart;
+}
+
+// Finding and inspecting applications and profiles.
+pub mod registry {
+    pub use crate::application::{
+        app_already_running, app_dir_by_name, app_pids_by_name, check_app_exists,
+        get_app_failed_state, get_app_health, get_app_info, get_app_socket, resolve_app_names,
+        wait_app_exit,
+    };
+    pub use crate::crescent::{
+        crescent_dir, get_app_names, get_apps_dir, get_profile, get_profiles_dir,
+    };
+}
+
+// Runs the `cres` command line, printing the error like returning it from `main` would.
+#[doc(hidden)]
+pub fn run_cli() -> ExitCode {
+    match cli::run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::output;
+
 use chrono::Local;
 use log::{Log, Metadata, Record};
 
@@ -9,11 +11,14 @@ impl Log for Logger {
     }
 
     fn log(&self, record: &Record) {
-        eprintln!(
-            "[{}] [crescent] {} - {}",
-            Local::now().time().format("%H:%M:%S"),
-            record.level(),
-            record.args()
+        output::write_line(
+            output::Source::Supervisor,
+            format!(
+                "[{}] [crescent] {} - {}",
+                Local::now().time().format("%H:%M:%S"),
+                record.level(),
+                record.args()
+            ),
         );
     }
 
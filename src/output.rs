@@ -0,0 +1,327 @@
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use crossbeam::channel::{bounded, Receiver, RecvTimeoutError, Sender, TrySendError};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// Lines a subscriber can fall behind before it is dropped.
+const SUBSCRIBER_BUFFER: usize = 4096;
+
+// Longest a capture can take, even if the subprocess never stops writing.
+const MAX_CAPTURE_TIME: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Subprocess,
+    Supervisor,
+}
+
+// The log of a supervisor and the clients subscribed to its output.
+pub struct Sink {
+    // Stderr if not set, which is the log file of a daemonized supervisor.
+    log: Option<Mutex<File>>,
+    subscribers: Mutex<Vec<Sender<(Source, String)>>>,
+    // Set once the application stopped, subscribers are not accepted anymore.
+    closed: AtomicBool,
+}
+
+impl Sink {
+    // Used by the daemon, which supervises every application from the same process.
+    pub fn new(log: File) -> Sink {
+        Sink {
+            log: Some(Mutex::new(log)),
+            subscribers: Mutex::new(Vec::new()),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    fn write_line(&self, source: Source, line: String) {
+        match &self.log {
+            Some(log) => {
+                let _ = writeln!(log.lock().unwrap(), "{line}");
+            }
+            None => eprintln!("{line}"),
+        }
+
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        // Subscribers that disconnected or can't keep up are removed, closing their channel.
+        subscribers.retain(
+            |subscriber| match subscriber.try_send((source, line.clone())) {
+                Ok(_) => true,
+                Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => false,
+            },
+        );
+    }
+}
+
+static STDERR: Sink = Sink {
+    log: None,
+    subscribers: Mutex::new(Vec::new()),
+    closed: AtomicBool::new(false),
+};
+
+thread_local! {
+    // Set in the threads of an application supervised by the daemon.
+    static SINK: RefCell<Option<Arc<Sink>>> = const { RefCell::new(None) };
+}
+
+fn with_sink<T, F: FnOnce(&Sink) -> T>(f: F) -> T {
+    SINK.with(|sink| match sink.borrow().as_ref() {
+        Some(sink) => f(sink),
+        None => f(&STDERR),
+    })
+}
+
+// Lines written from this thread, and the ones spawned with `spawn`, go to the sink.
+pub fn set_sink(sink: Arc<Sink>) {
+    SINK.with(|current| *current.borrow_mut() = Some(sink));
+}
+
+// Spawns a named thread writing to the same sink as the current one.
+pub fn spawn<T, F>(name: &str, f: F) -> io::Result<JoinHandle<T>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let sink = SINK.with(|sink| sink.borrow().clone());
+
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            if let Some(sink) = sink {
+                set_sink(sink);
+            }
+
+            f()
+        })
+}
+
+// Options for collecting the output that follows a command.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Capture {
+    // Stops after the first line matching this regex, including it.
+    pub until: Option<String>,
+    // Stops when no line is written for this many seconds.
+    pub idle_timeout: u64,
+    pub max_lines: Option<usize>,
+}
+
+impl Capture {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Some(until) = &self.until {
+            Regex::new(until).map_err(|err| anyhow!("Invalid regex '{until}': {err}."))?;
+        }
+
+        if self.max_lines == Some(0) {
+            return Err(anyhow!("Line count has to be greater than 0."));
+        }
+
+        Ok(())
+    }
+
+    // Collects subprocess lines from the receiver, returns them and if `until` matched.
+    pub(crate) fn collect(
+        &self,
+        receiver: &Receiver<(Source, String)>,
+    ) -> Result<(Vec<String>, bool)> {
+        let until = match &self.until {
+            Some(until) => Some(Regex::new(until)?),
+            None => None,
+        };
+
+        let idle_timeout = Duration::from_secs(self.idle_timeout);
+        let started_at = Instant::now();
+        let mut lines = vec![];
+        let mut last_line_at = Instant::now();
+
+        loop {
+            let remaining = idle_timeout
+                .saturating_sub(last_line_at.elapsed())
+                .min(MAX_CAPTURE_TIME.saturating_sub(started_at.elapsed()));
+
+            let (source, line) = match receiver.recv_timeout(remaining) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return Ok((lines, false)),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(anyhow!("Too far behind the application output."))
+                }
+            };
+
+            // Lines from the supervisor itself don't reset the idle timeout.
+            if source == Source::Supervisor {
+                continue;
+            }
+
+            last_line_at = Instant::now();
+
+            let matched = until.as_ref().is_some_and(|until| until.is_match(&line));
+            lines.push(line);
+
+            if matched {
+                return Ok((lines, true));
+            }
+
+            if self
+                .max_lines
+                .is_some_and(|max_lines| lines.len() >= max_lines)
+            {
+                return Ok((lines, false));
+            }
+        }
+    }
+}
+
+// Writes a line to the log and sends it to every subscriber of the current sink.
+pub fn write_line(source: Source, line: String) {
+    with_sink(|sink| sink.write_line(source, line));
+}
+
+// Receives every line written to the current sink after subscribing.
+// The channel is already closed if the application stopped.
+pub fn subscribe() -> Receiver<(Source, String)> {
+    let (sender, receiver) = bounded(SUBSCRIBER_BUFFER);
+
+    with_sink(|sink| {
+        let mut subscribers = sink.subscribers.lock().unwrap();
+
+        if !sink.closed.load(Ordering::SeqCst) {
+            subscribers.push(sender);
+        }
+    });
+
+    receiver
+}
+
+// Closes the channel of every subscriber, the sink of the daemon outlives the application
+// and the subscriber threads would wait for lines forever.
+pub fn close_subscribers() {
+    with_sink(|sink| {
+        let mut subscribers = sink.subscribers.lock().unwrap();
+        sink.closed.store(true, Ordering::SeqCst);
+        subscribers.clear();
+    });
+}
+
+// True if the channels were closed because the application stopped.
+pub fn is_closed() -> bool {
+    with_sink(|sink| sink.closed.load(Ordering::SeqCst))
+}
+
+// Reads the subprocess output until it is closed, writing every line.
+pub fn forward<R: Read + Send + 'static>(output: R) -> Result<()> {
+    spawn("output", move || {
+        let mut reader = BufReader::new(output);
+        let mut buffer = vec![];
+
+        loop {
+            buffer.clear();
+
+            match reader.read_until(b'\n', &mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buffer);
+                    let line = line.trim_end_matches(['\r', '\n']).to_string();
+                    write_line(Source::Subprocess, line);
+                }
+            }
+        }
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Cursor, time::Duration};
+
+    #[test]
+    fn unit_output_forward() -> Result<()> {
+        let receiver = subscribe();
+
+        forward(Cursor::new(b"first\r\nsecond\n\xffthird".to_vec()))?;
+
+        let timeout = Duration::from_secs(5);
+        let mut lines = vec![];
+
+        while lines.len() < 3 {
+            let (source, line) = receiver.recv_timeout(timeout)?;
+
+            // Other tests in this process may be logging too.
+            if source == Source::Subprocess
+                && ["first", "second", "\u{fffd}third"].contains(&line.as_str())
+            {
+                lines.push(line);
+            }
+        }
+
+        assert_eq!(lines, ["first", "second", "\u{fffd}third"]);
+        Ok(())
+    }
+
+    #[test]
+    fn unit_output_capture_collect() -> Result<()> {
+        let (sender, receiver) = bounded(16);
+
+        let send = |lines: &[(Source, &str)]| {
+            for (source, line) in lines {
+                sender.send((*source, line.to_string())).unwrap();
+            }
+        };
+
+        let capture = Capture {
+            until: Some(String::from("^There are \\d+ players")),
+            idle_timeout: 1,
+            max_lines: None,
+        };
+        capture.validate()?;
+
+        send(&[
+            (
+                Source::Supervisor,
+                "[crescent] INFO - Command received: 'list'",
+            ),
+            (Source::Subprocess, "Listing players"),
+            (Source::Subprocess, "There are 2 players online"),
+            (Source::Subprocess, "Not captured"),
+        ]);
+
+        let (lines, matched) = capture.collect(&receiver)?;
+        assert_eq!(lines, ["Listing players", "There are 2 players online"]);
+        assert!(matched);
+
+        let capture = Capture {
+            until: None,
+            idle_timeout: 1,
+            max_lines: Some(1),
+        };
+
+        let (lines, matched) = capture.collect(&receiver)?;
+        assert_eq!(lines, ["Not captured"]);
+        assert!(!matched);
+
+        // Nothing else is written, stopping after the idle timeout.
+        let (lines, _) = capture.collect(&receiver)?;
+        assert!(lines.is_empty());
+
+        let capture = Capture {
+            until: Some(String::from("(")),
+            idle_timeout: 1,
+            max_lines: Some(0),
+        };
+        assert!(capture.validate().is_err());
+        Ok(())
+    }
+}
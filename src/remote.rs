@@ -0,0 +1,252 @@
+use std::{
+    fmt::Display,
+    fs,
+    io::{self, ErrorKind, Read},
+    net::{TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{self, Error},
+    socket::{self, Message, Stream},
+    subprocess::SocketEvent,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+// Shorter tokens are rejected, they would be too easy to guess.
+const MIN_TOKEN_LENGTH: usize = 16;
+
+// Longer tokens are rejected, the authentication frame is kept small.
+const MAX_TOKEN_LENGTH: usize = 1024;
+
+// Clients have this long to authenticate before being disconnected.
+pub const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Largest frame accepted before a client authenticated, enough for the longest token.
+pub const AUTH_MAX_FRAME_SIZE: usize = 4 * 1024;
+
+static REMOTE: OnceLock<Remote> = OnceLock::new();
+
+// Opt-in TCP listener, accepting the same events as the application socket.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RemoteListener {
+    // Address and port to listen on, like '127.0.0.1:7500'.
+    pub listen: String,
+    // File containing the token clients authenticate with, so it is not kept in the profile.
+    pub token_file: PathBuf,
+    // The token and events are sent in plain text, other addresses than loopback
+    // are refused unless set, the listener should be behind an SSH tunnel or a TLS proxy.
+    #[serde(default)]
+    pub allow_public: bool,
+}
+
+impl RemoteListener {
+    pub(crate) fn validate(&self) -> Result<()> {
+        let mut addresses = self
+            .listen
+            .to_socket_addrs()
+            .context(format!("Invalid listen address '{}'.", self.listen))?;
+
+        if !self.allow_public && !addresses.all(|address| address.ip().is_loopback()) {
+            return Err(anyhow!(
+                "The remote listener is not encrypted, listen on a loopback address and use \
+                 an SSH tunnel or a TLS proxy, or set 'allow_public' to listen on '{}'.",
+                self.listen
+            ));
+        }
+
+        read_token(&self.token_file)?;
+        Ok(())
+    }
+
+    pub(crate) fn token(&self) -> Result<String> {
+        read_token(&self.token_file)
+    }
+}
+
+impl Display for RemoteListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, token from '{}'",
+            self.listen,
+            self.token_file.display()
+        )
+    }
+}
+
+pub fn read_token(path: &PathBuf) -> Result<String> {
+    let token = fs::read_to_string(path)
+        .context(format!("Error reading token file '{}'.", path.display()))?
+        .trim()
+        .to_string();
+
+    if token.len() < MIN_TOKEN_LENGTH {
+        return Err(anyhow!(
+            "Token in '{}' has to be at least {MIN_TOKEN_LENGTH} characters long.",
+            path.display()
+        ));
+    }
+
+    if token.len() > MAX_TOKEN_LENGTH {
+        return Err(anyhow!(
+            "Token in '{}' has to be at most {MAX_TOKEN_LENGTH} characters long.",
+            path.display()
+        ));
+    }
+
+    Ok(token)
+}
+
+// Reads until a deadline instead of with a timeout per read, so a client sending
+// a byte at a time can't stay connected without authenticating.
+pub struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl<'a> DeadlineReader<'a> {
+    pub fn new(stream: &'a TcpStream, timeout: Duration) -> DeadlineReader<'a> {
+        DeadlineReader {
+            stream,
+            deadline: Instant::now() + timeout,
+        }
+    }
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "Deadline reached."));
+        }
+
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+// Compares every byte, taking the same time wherever the tokens differ.
+pub fn tokens_match(received: &str, expected: &str) -> bool {
+    if received.len() != expected.len() {
+        return false;
+    }
+
+    received
+        .bytes()
+        .zip(expected.bytes())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+// A supervisor listening on another host, set with the global `--remote` argument.
+pub struct Remote {
+    pub address: String,
+    pub token: String,
+}
+
+impl Remote {
+    // Connects and authenticates, the stream is then used like the application socket.
+    pub fn connect(&self) -> error::Result<Stream> {
+        let stream = TcpStream::connect(&self.address).map_err(|err| Error::Connection {
+            target: format!("'{}'", self.address),
+            source: Some(Box::new(err)),
+        })?;
+
+        let mut stream = Stream::Tcp(stream);
+        stream.set_read_timeout(Some(AUTH_TIMEOUT))?;
+
+        let request = Message::new(SocketEvent::Auth(self.token.clone()));
+        socket::write_event(&mut stream, &request)?;
+
+        let response: Message =
+            socket::read_event(&mut stream)?.ok_or_else(|| Error::Connection {
+                target: format!("'{}', connection closed", self.address),
+                source: None,
+            })?;
+
+        match response.event {
+            SocketEvent::Ack => {}
+            SocketEvent::Error(err) => return Err(Error::Application(err)),
+            _ => return Err(Error::unexpected_response()),
+        }
+
+        stream.set_read_timeout(None)?;
+        Ok(stream)
+    }
+}
+
+pub fn set(remote: Remote) {
+    let _ = REMOTE.set(remote);
+}
+
+pub fn get() -> Option<&'static Remote> {
+    REMOTE.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env::temp_dir, fs::remove_file};
+
+    #[test]
+    fn unit_remote_listener_validate() -> Result<()> {
+        let token_file = temp_dir().join("crescent_remote_token_test");
+        fs::write(&token_file, "0123456789abcdef\n")?;
+
+        let listener = RemoteListener {
+            listen: String::from("127.0.0.1:7500"),
+            token_file: token_file.clone(),
+            allow_public: false,
+        };
+        listener.validate()?;
+        assert_eq!(listener.token()?, "0123456789abcdef");
+
+        let public = RemoteListener {
+            listen: String::from("0.0.0.0:7500"),
+            ..listener.clone()
+        };
+        let err = public.validate().unwrap_err();
+        assert!(format!("{}", err).contains("set 'allow_public' to listen on '0.0.0.0:7500'"));
+
+        RemoteListener {
+            allow_public: true,
+            ..public
+        }
+        .validate()?;
+
+        fs::write(&token_file, "short")?;
+        let err = listener.validate().unwrap_err();
+        assert!(format!("{}", err).contains("at least 16 characters"));
+
+        fs::write(&token_file, "0123456789abcdef".repeat(100))?;
+        let err = listener.validate().unwrap_err();
+        assert!(format!("{}", err).contains("at most 1024 characters"));
+
+        let listener = RemoteListener {
+            listen: String::from("not an address"),
+            ..listener
+        };
+        let err = listener.validate().unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Invalid listen address 'not an address'."
+        );
+
+        remove_file(token_file)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unit_tokens_match() {
+        assert!(tokens_match("0123456789abcdef", "0123456789abcdef"));
+        assert!(!tokens_match("0123456789abcdeF", "0123456789abcdef"));
+        assert!(!tokens_match("0123456789abcde", "0123456789abcdef"));
+        assert!(!tokens_match("", "0123456789abcdef"));
+    }
+}
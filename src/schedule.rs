@@ -0,0 +1,427 @@
+use std::{
+    collections::BTreeMap,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::output;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local};
+use croner::Cron;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+// Longest sleep between checks, keeps the next runs close to the wall clock.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduledRestart {
+    pub cron: String,
+    // Offsets before the restart such as '10m', '5m', '1m' and '10s'.
+    pub warnings: Option<Vec<String>>,
+    // Sent for every warning, '{remaining}' is replaced with the time left.
+    pub warning_command: Option<String>,
+}
+
+impl ScheduledRestart {
+    pub(crate) fn validate(&self) -> Result<()> {
+        next_run(&self.cron, &Local::now())?;
+
+        let warnings = self.warnings()?;
+
+        // Subtracted from the restart time, they have to fit in a chrono duration.
+        for warning in &warnings {
+            chrono::Duration::from_std(*warning)
+                .map_err(|_| anyhow!("Scheduled restart warning {warning:?} is too long."))?;
+        }
+
+        if !warnings.is_empty() && self.warning_command.is_none() {
+            return Err(anyhow!(
+                "Scheduled restart warnings need a 'warning_command'."
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Parsed warning offsets, from the longest to the shortest.
+    pub(crate) fn warnings(&self) -> Result<Vec<Duration>> {
+        let mut warnings = self
+            .warnings
+            .iter()
+            .flatten()
+            .map(|offset| parse_offset(offset))
+            .collect::<Result<Vec<Duration>>>()?;
+
+        warnings.sort();
+        warnings.reverse();
+
+        Ok(warnings)
+    }
+
+    pub fn warning(&self, remaining: Duration) -> Option<String> {
+        let command = self.warning_command.as_ref()?;
+        Some(command.replace("{remaining}", &format_remaining(remaining)))
+    }
+}
+
+// Accepts the usual 5 fields and an optional leading seconds field.
+pub fn parse_cron(expression: &str) -> Result<Cron> {
+    Cron::new(expression)
+        .with_seconds_optional()
+        .parse()
+        .map_err(|err| anyhow!("Invalid cron expression '{expression}': {err}."))
+}
+
+pub fn next_run(expression: &str, after: &DateTime<Local>) -> Result<DateTime<Local>> {
+    parse_cron(expression)?
+        .find_next_occurrence(after, false)
+        .map_err(|err| anyhow!("Error finding next run of '{expression}': {err}."))
+}
+
+pub fn validate(schedules: &BTreeMap<String, String>) -> Result<()> {
+    let now = Local::now();
+
+    // Expressions that parse but never match, like February 30th, are rejected as well.
+    for (expression, command) in schedules {
+        next_run(expression, &now)?;
+
+        if command.trim().is_empty() {
+            return Err(anyhow!("Command for schedule '{expression}' is empty."));
+        }
+    }
+
+    Ok(())
+}
+
+// Runs `run_command` with each command when its cron expression is due,
+// until `should_stop` returns true.
+pub fn start_schedules<R, S>(
+    schedules: BTreeMap<String, String>,
+    run_command: R,
+    should_stop: S,
+) -> Result<()>
+where
+    R: Fn(String) -> Result<()> + Send + 'static,
+    S: Fn() -> bool + Send + 'static,
+{
+    let mut jobs = vec![];
+    let now = Local::now();
+
+    for (expression, command) in schedules {
+        let cron = parse_cron(&expression)?;
+        let next = cron
+            .find_next_occurrence(&now, false)
+            .map_err(|err| anyhow!("Error finding next run of '{expression}': {err}."))?;
+
+        jobs.push((cron, command, next));
+    }
+
+    output::spawn("schedules", move || loop {
+        let now = Local::now();
+
+        for (cron, command, next) in jobs.iter_mut() {
+            if *next > now {
+                continue;
+            }
+
+            info!("Running scheduled command: '{command}'");
+            if let Err(err) = run_command(command.clone()) {
+                error!("Error running scheduled command '{command}': {err}");
+            }
+
+            match cron.find_next_occurrence(&now, false) {
+                Ok(time) => *next = time,
+                Err(err) => error!("Error finding next run of '{command}': {err}."),
+            }
+        }
+
+        let sleep = jobs
+            .iter()
+            .map(|(_, _, next)| (*next - now).to_std().unwrap_or_default())
+            .min()
+            .unwrap_or(MAX_SLEEP)
+            .min(MAX_SLEEP);
+
+        // Checked while sleeping, a command is never sent after `should_stop` returned true.
+        if !sleep_for(sleep, &should_stop) {
+            return;
+        }
+    })?;
+
+    Ok(())
+}
+
+// Sends every warning before the next restart time, then calls `restart`,
+// until `should_stop` returns true.
+pub fn start_scheduled_restart<W, R, S>(
+    scheduled_restart: ScheduledRestart,
+    send_warning: W,
+    restart: R,
+    should_stop: S,
+) -> Result<()>
+where
+    W: Fn(String) -> Result<()> + Send + 'static,
+    R: Fn() + Send + 'static,
+    S: Fn() -> bool + Send + 'static,
+{
+    let cron = parse_cron(&scheduled_restart.cron)?;
+    let warnings = scheduled_restart.warnings()?;
+
+    output::spawn("scheduled_restart", move || loop {
+        let now = Local::now();
+
+        let restart_at = match cron.find_next_occurrence(&now, false) {
+            Ok(time) => time,
+            Err(err) => {
+                error!("Error finding next scheduled restart: {err}.");
+                return;
+            }
+        };
+
+        info!("Next scheduled restart at {restart_at}.");
+
+        for remaining in &warnings {
+            let warning_at = match chrono::Duration::from_std(*remaining)
+                .ok()
+                .and_then(|remaining| restart_at.checked_sub_signed(remaining))
+            {
+                Some(time) => time,
+                None => continue,
+            };
+
+            // Warnings longer than the time left are skipped.
+            if warning_at < now {
+                continue;
+            }
+
+            if !sleep_until(&warning_at, &should_stop) {
+                return;
+            }
+
+            if let Some(warning) = scheduled_restart.warning(*remaining) {
+                info!("Sending restart warning: '{warning}'");
+                if let Err(err) = send_warning(warning) {
+                    error!("Error sending restart warning: {err}");
+                }
+            }
+        }
+
+        if !sleep_until(&restart_at, &should_stop) {
+            return;
+        }
+
+        info!("Running scheduled restart.");
+        restart();
+    })?;
+
+    Ok(())
+}
+
+// Returns false if `should_stop` returned true before the time was reached.
+fn sleep_until<S: Fn() -> bool>(time: &DateTime<Local>, should_stop: &S) -> bool {
+    loop {
+        if should_stop() {
+            return false;
+        }
+
+        let remaining = (*time - Local::now()).to_std().unwrap_or_default();
+
+        if remaining.is_zero() {
+            return true;
+        }
+
+        thread::sleep(remaining.min(Duration::from_secs(1)));
+    }
+}
+
+// Checks `should_stop` every second, returns false if it returned true.
+fn sleep_for<S: Fn() -> bool>(duration: Duration, should_stop: &S) -> bool {
+    let deadline = Instant::now() + duration;
+
+    loop {
+        if should_stop() {
+            return false;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return true;
+        }
+
+        thread::sleep(remaining.min(Duration::from_secs(1)));
+    }
+}
+
+// Parses offsets like '1h', '10m', '30s' or just seconds.
+pub fn parse_offset(offset: &str) -> Result<Duration> {
+    let offset = offset.trim();
+
+    let (value, unit) = match offset.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => offset.split_at(index),
+        None => (offset, "s"),
+    };
+
+    let value: u64 = value
+        .parse()
+        .context(format!("Invalid offset '{offset}'."))?;
+
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => return Err(anyhow!("Invalid offset '{offset}', use 's', 'm' or 'h'.")),
+    };
+
+    let seconds = value
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("Offset '{offset}' is too long."))?;
+
+    Ok(Duration::from_secs(seconds))
+}
+
+// Formats the time left for warnings, like '10 minutes' or '1 minute 30 seconds'.
+pub fn format_remaining(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+
+    let units = [
+        (seconds / 3600, "hour"),
+        (seconds % 3600 / 60, "minute"),
+        (seconds % 60, "second"),
+    ];
+
+    let parts: Vec<String> = units
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| match value {
+            1 => format!("1 {unit}"),
+            _ => format!("{value} {unit}s"),
+        })
+        .collect();
+
+    if parts.is_empty() {
+        return String::from("0 seconds");
+    }
+
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Timelike};
+
+    #[test]
+    fn unit_schedule_next_run() -> Result<()> {
+        let now = Local.with_ymd_and_hms(2024, 1, 1, 10, 3, 20).unwrap();
+
+        let next = next_run("*/10 * * * *", &now)?;
+        assert_eq!((next.hour(), next.minute(), next.second()), (10, 10, 0));
+
+        let next = next_run("0 * * * *", &now)?;
+        assert_eq!((next.hour(), next.minute()), (11, 0));
+
+        let next = next_run("*/30 * * * * *", &now)?;
+        assert_eq!((next.minute(), next.second()), (3, 30));
+
+        let err = next_run("not a cron", &now).unwrap_err();
+        assert!(format!("{}", err).starts_with("Invalid cron expression 'not a cron'"));
+        Ok(())
+    }
+
+    #[test]
+    fn unit_scheduled_restart_warnings() -> Result<()> {
+        let json = r#"{"cron": "0 4 * * *", "warnings": ["1m", "10s", "10m", "5m"], "warning_command": "say Restarting in {remaining}"}"#;
+        let scheduled_restart: ScheduledRestart = serde_json::from_str(json)?;
+        scheduled_restart.validate()?;
+
+        let warnings = scheduled_restart.warnings()?;
+        assert_eq!(
+            warnings,
+            [600, 300, 60, 10].map(Duration::from_secs).to_vec()
+        );
+
+        assert_eq!(
+            scheduled_restart.warning(warnings[0]),
+            Some(String::from("say Restarting in 10 minutes"))
+        );
+
+        let scheduled_restart = ScheduledRestart {
+            cron: String::from("0 4 * * *"),
+            warnings: Some(vec![String::from("10m")]),
+            warning_command: None,
+        };
+        let err = scheduled_restart.validate().unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Scheduled restart warnings need a 'warning_command'."
+        );
+
+        let scheduled_restart = ScheduledRestart {
+            cron: String::from("0 4 * * *"),
+            warnings: Some(vec![String::from("5000000000000h")]),
+            warning_command: Some(String::from("say Restarting in {remaining}")),
+        };
+        let err = scheduled_restart.validate().unwrap_err();
+        assert!(format!("{}", err).ends_with("is too long."));
+        Ok(())
+    }
+
+    #[test]
+    fn unit_parse_offset() -> Result<()> {
+        assert_eq!(parse_offset("10s")?, Duration::from_secs(10));
+        assert_eq!(parse_offset("5m")?, Duration::from_secs(300));
+        assert_eq!(parse_offset("1h")?, Duration::from_secs(3600));
+        assert_eq!(parse_offset("45")?, Duration::from_secs(45));
+
+        let err = parse_offset("5d").unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Invalid offset '5d', use 's', 'm' or 'h'."
+        );
+        assert!(parse_offset("m").is_err());
+
+        let err = parse_offset("6000000000000000h").unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Offset '6000000000000000h' is too long."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unit_format_remaining() {
+        assert_eq!(format_remaining(Duration::from_secs(600)), "10 minutes");
+        assert_eq!(format_remaining(Duration::from_secs(60)), "1 minute");
+        assert_eq!(
+            format_remaining(Duration::from_secs(90)),
+            "1 minute 30 seconds"
+        );
+        assert_eq!(
+            format_remaining(Duration::from_secs(3601)),
+            "1 hour 1 second"
+        );
+        assert_eq!(format_remaining(Duration::ZERO), "0 seconds");
+    }
+
+    #[test]
+    fn unit_schedule_validate() -> Result<()> {
+        let mut schedules = BTreeMap::new();
+        schedules.insert(String::from("*/10 * * * *"), String::from("save-all"));
+        validate(&schedules)?;
+
+        schedules.insert(String::from("0 * * * *"), String::from(" "));
+        let err = validate(&schedules).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Command for schedule '0 * * * *' is empty."
+        );
+
+        let schedules = BTreeMap::from([(String::from("0 0 30 2 *"), String::from("save-all"))]);
+        let err = validate(&schedules).unwrap_err();
+        assert!(format!("{}", err).starts_with("Error finding next run of '0 0 30 2 *'"));
+        Ok(())
+    }
+}
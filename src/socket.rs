@@ -0,0 +1,314 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::TcpStream,
+    os::unix::net::UnixStream,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    error::{Error, Result},
+    subprocess::SocketEvent,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+// Frames bigger than this are rejected instead of allocating for them.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+// Increased on breaking changes to the framing or `SocketEvent`,
+// supervisors from before the handshake used unframed JSON.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+pub const CAPABILITY_STOP: &str = "stop";
+pub const CAPABILITY_HEALTH: &str = "health";
+pub const CAPABILITY_OUTPUT: &str = "output";
+pub const CAPABILITY_CAPTURE: &str = "capture";
+
+// Features a supervisor from this version supports, other than the basic events.
+const CAPABILITIES: &[&str] = &[
+    CAPABILITY_STOP,
+    CAPABILITY_HEALTH,
+    CAPABILITY_OUTPUT,
+    CAPABILITY_CAPTURE,
+];
+
+// Exchanged by the client and the supervisor to find out what the other side supports.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Hello {
+    pub version: String,
+    pub protocol: u32,
+    pub capabilities: Vec<String>,
+}
+
+impl Hello {
+    pub fn current() -> Hello {
+        Hello {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol: PROTOCOL_VERSION,
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    // Used when the supervisor does not answer the handshake.
+    pub fn legacy() -> Hello {
+        Hello {
+            version: String::from("unknown"),
+            protocol: 1,
+            capabilities: vec![],
+        }
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.protocol == PROTOCOL_VERSION && self.capabilities.iter().any(|c| c == capability)
+    }
+
+    pub fn is_current(&self) -> bool {
+        *self == Hello::current()
+    }
+}
+
+// Used in responses to requests that could not be parsed.
+pub const UNKNOWN_REQUEST_ID: u64 = 0;
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+pub fn next_request_id() -> u64 {
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// Every event is sent with an ID, the supervisor answers with the ID of the request.
+#[derive(Serialize, Deserialize)]
+pub struct Message {
+    pub id: u64,
+    pub event: SocketEvent,
+}
+
+impl Message {
+    pub fn new(event: SocketEvent) -> Message {
+        Message {
+            id: next_request_id(),
+            event,
+        }
+    }
+}
+
+// Finds the ID of a request that could not be parsed as a `Message`,
+// for example one with an event from a newer version.
+pub fn request_id(frame: &[u8]) -> u64 {
+    serde_json::from_slice::<serde_json::Value>(frame)
+        .ok()
+        .and_then(|value| value.get("id")?.as_u64())
+        .unwrap_or(UNKNOWN_REQUEST_ID)
+}
+
+// A connection to a supervisor, through its socket or its remote listener.
+#[derive(Debug)]
+pub enum Stream {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Stream {
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    pub fn try_clone(&self) -> io::Result<Stream> {
+        match self {
+            Stream::Unix(stream) => Ok(Stream::Unix(stream.try_clone()?)),
+            Stream::Tcp(stream) => Ok(Stream::Tcp(stream.try_clone()?)),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Unix(stream) => stream.read(buf),
+            Stream::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Unix(stream) => stream.write(buf),
+            Stream::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Unix(stream) => stream.flush(),
+            Stream::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+// Every message is a big-endian u32 length followed by that many bytes of JSON.
+pub fn write_event<W: Write, T: Serialize>(writer: &mut W, event: &T) -> Result<()> {
+    let json = serde_json::to_vec(event)?;
+
+    if json.len() > MAX_FRAME_SIZE {
+        return Err(Error::Protocol(String::from(
+            "Event is too large to be sent.",
+        )));
+    }
+
+    // A single write so frames from different threads are not interleaved.
+    let mut frame = Vec::with_capacity(4 + json.len());
+    frame.extend_from_slice(&(json.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&json);
+
+    writer.write_all(&frame)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+// Returns None if the connection was closed before a new frame started.
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    read_frame_with_limit(reader, MAX_FRAME_SIZE)
+}
+
+// Like `read_frame`, with a lower limit for clients that are not trusted yet.
+pub fn read_frame_with_limit<R: Read>(reader: &mut R, limit: usize) -> Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+
+    match reader.read_exact(&mut length) {
+        Ok(_) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    let length = u32::from_be_bytes(length) as usize;
+
+    if length > limit {
+        return Err(Error::Protocol(format!(
+            "Received frame of {length} bytes, larger than the limit."
+        )));
+    }
+
+    let mut frame = vec![0u8; length];
+    reader
+        .read_exact(&mut frame)
+        .map_err(Error::io("Connection closed in the middle of a frame."))?;
+
+    Ok(Some(frame))
+}
+
+pub fn read_event<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<Option<T>> {
+    read_event_with_limit(reader, MAX_FRAME_SIZE)
+}
+
+pub fn read_event_with_limit<R: Read, T: DeserializeOwned>(
+    reader: &mut R,
+    limit: usize,
+) -> Result<Option<T>> {
+    match read_frame_with_limit(reader, limit)? {
+        Some(frame) => Ok(Some(serde_json::from_slice(&frame)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn unit_socket_pipelined_events() -> Result<()> {
+        let long_command = "-XX:+UseG1GC ".repeat(500);
+
+        let mut buffer = vec![];
+        write_event(&mut buffer, &SocketEvent::Ping)?;
+        write_event(&mut buffer, &SocketEvent::WriteStdin(long_command.clone()))?;
+        write_event(
+            &mut buffer,
+            &SocketEvent::CommandHistory(vec![long_command.clone(); 10]),
+        )?;
+
+        assert!(buffer.len() > 1024 * 10);
+
+        let mut reader = Cursor::new(buffer);
+
+        assert!(matches!(read_event(&mut reader)?, Some(SocketEvent::Ping)));
+
+        match read_event(&mut reader)? {
+            Some(SocketEvent::WriteStdin(command)) => assert_eq!(command, long_command),
+            _ => panic!("Expected WriteStdin."),
+        }
+
+        match read_event(&mut reader)? {
+            Some(SocketEvent::CommandHistory(history)) => assert_eq!(history.len(), 10),
+            _ => panic!("Expected CommandHistory."),
+        }
+
+        assert!(read_event::<_, SocketEvent>(&mut reader)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn unit_socket_hello() {
+        let hello = Hello::current();
+        assert!(hello.is_current());
+        assert!(hello.supports(CAPABILITY_STOP));
+        assert!(!hello.supports("does_not_exist"));
+
+        let legacy = Hello::legacy();
+        assert!(!legacy.is_current());
+        assert!(!legacy.supports(CAPABILITY_STOP));
+
+        // Capabilities from a different protocol can't be trusted.
+        let hello = Hello {
+            protocol: PROTOCOL_VERSION + 1,
+            ..Hello::current()
+        };
+        assert!(!hello.supports(CAPABILITY_STOP));
+    }
+
+    #[test]
+    fn unit_socket_invalid_frames() -> Result<()> {
+        let mut buffer = vec![];
+        write_event(&mut buffer, &SocketEvent::Ping)?;
+        buffer.truncate(buffer.len() - 1);
+
+        let err = read_frame(&mut Cursor::new(buffer)).unwrap_err();
+        assert_eq!(
+            format!("{}", err),
+            "Connection closed in the middle of a frame."
+        );
+
+        let buffer = u32::MAX.to_be_bytes().to_vec();
+        assert!(read_frame(&mut Cursor::new(buffer)).is_err());
+
+        let mut buffer = 3u32.to_be_bytes().to_vec();
+        buffer.extend_from_slice(b"bad");
+        assert!(read_event::<_, SocketEvent>(&mut Cursor::new(buffer)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn unit_socket_messages() -> Result<()> {
+        let first = Message::new(SocketEvent::Ping);
+        let second = Message::new(SocketEvent::WriteStdin(String::from("ping")));
+        assert!(second.id > first.id);
+        assert_ne!(first.id, UNKNOWN_REQUEST_ID);
+
+        let mut buffer = vec![];
+        write_event(&mut buffer, &second)?;
+
+        let message: Message = read_event(&mut Cursor::new(buffer))?.unwrap();
+        assert_eq!(message.id, second.id);
+        assert!(matches!(message.event, SocketEvent::WriteStdin(command) if command == "ping"));
+
+        assert_eq!(request_id(br#"{"id": 7, "event": "DoesNotExist"}"#), 7);
+        assert_eq!(request_id(b"bad"), UNKNOWN_REQUEST_ID);
+        Ok(())
+    }
+}
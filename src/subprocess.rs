@@ -1,29 +1,168 @@
 use std::{
-    ffi::c_int,
-    fs::{self, File},
+    collections::VecDeque,
+    ffi::{c_int, OsString},
+    fmt::Display,
+    fs::{self, File, Permissions},
     io::{Error, ErrorKind, Read, Write},
-    os::unix::net::{UnixListener, UnixStream},
-    path::PathBuf,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process,
     sync::{Arc, Mutex},
     thread,
+    time::{Duration, Instant},
 };
 
-use crate::application::Application;
+use crate::{
+    access::{AccessLevel, Peer},
+    application::{Application, FailedState},
+    environment,
+    health::{HealthAction, HealthCheck, HealthStatus},
+    history::{self, ExitRecord, HistoryEvent, StoppedBy},
+    output::{self, Capture, Source},
+    remote::{self, RemoteListener},
+    schedule,
+    socket::{self, Hello, Message},
+    tail::Tail,
+    util,
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use crossbeam::channel::{bounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
 use libc::pid_t;
-use log::{error, info};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
 use subprocess::{Popen, PopenConfig, Redirection};
 use sysinfo::Pid;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub enum SocketEvent {
     RetrieveAppInfo(Box<Application>),
     CommandHistory(Vec<String>),
     WriteStdin(String),
-    Stop,
+    Stop {
+        force: bool,
+        stopped_by: StoppedBy,
+        timeout: Option<u64>,
+    },
+    Stopped(StopStep),
+    Health(Option<HealthStatus>),
+    Hello(Hello),
     Ping,
+    // Answered with an `Ack`, followed by an `Output` for every new line in the log,
+    // the connection does not accept other requests after it.
+    Subscribe,
+    Output(String),
+    // Writes the command to stdin and answers with the output that follows it.
+    Capture {
+        command: String,
+        capture: Capture,
+    },
+    Captured {
+        lines: Vec<String>,
+        matched: bool,
+    },
+    // Sent with the token as the first event to the remote listener.
+    Auth(String),
+    // Success response for requests that have no other answer.
+    Ack,
+    Error(String),
+}
+
+// What made the subprocess exit after a stop was requested.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopStep {
+    NotRunning,
+    StopCommand,
+    Sigterm,
+    Sigkill,
+}
+
+impl Display for StopStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopStep::NotRunning => write!(f, "Subprocess was not running."),
+            StopStep::StopCommand => write!(f, "Subprocess stopped by the stop command."),
+            StopStep::Sigterm => write!(f, "Subprocess stopped by SIGTERM."),
+            StopStep::Sigkill => write!(f, "Subprocess stopped by SIGKILL."),
+        }
+    }
+}
+
+// Seconds to wait on each step of a stop, if not set by the client or profile.
+const DEFAULT_STOP_TIMEOUT: u64 = 30;
+
+const SIGKILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Lines from the log kept when an application is marked as failed.
+const FAILED_LOG_LINES: usize = 10;
+
+// Restarts are delayed at most by this amount.
+const MAX_RESTART_DELAY: u64 = 300;
+
+// A subprocess running for longer than this is considered stable,
+// resetting the restart attempts and its backoff.
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+// Keeps the time of recent exits to detect an application failing over and over.
+struct CrashLoop {
+    exits: VecDeque<Instant>,
+    limit: u32,
+    window: Duration,
+}
+
+impl CrashLoop {
+    fn new(limit: u32, window: Duration) -> CrashLoop {
+        CrashLoop {
+            exits: VecDeque::new(),
+            limit,
+            window,
+        }
+    }
+
+    // Returns true if the limit of exits was reached inside the window.
+    fn record_exit(&mut self, at: Instant) -> bool {
+        self.exits.push_back(at);
+
+        while let Some(first) = self.exits.front() {
+            if at.duration_since(*first) <= self.window {
+                break;
+            }
+            self.exits.pop_front();
+        }
+
+        self.exits.len() >= self.limit as usize
+    }
+}
+
+// Disconnected once the supervisor shuts down, the helper threads wait on it instead of sleeping.
+#[derive(Clone)]
+struct Shutdown(Receiver<()>);
+
+impl Shutdown {
+    fn is_set(&self) -> bool {
+        self.0.try_recv() == Err(TryRecvError::Disconnected)
+    }
+
+    // Returns false if the supervisor shut down before the duration passed.
+    fn wait(&self, duration: Duration) -> bool {
+        self.0.recv_timeout(duration) != Err(RecvTimeoutError::Disconnected)
+    }
+}
+
+#[derive(Default)]
+struct SubprocessState {
+    pid: Option<Pid>,
+    // Locked separately, a subprocess not reading its stdin only blocks other writers.
+    stdin: Option<Arc<Mutex<File>>>,
+    stop_requested: Option<StoppedBy>,
+    // Set by a failing health check or a scheduled restart,
+    // restarting regardless of the restart policy.
+    restart_requested: Option<StoppedBy>,
+    health: Option<HealthStatus>,
 }
 
 pub fn start(app_info: Application, app_dir: PathBuf) -> Result<()> {
@@ -31,21 +170,48 @@ pub fn start(app_info: Application, app_dir: PathBuf) -> Result<()> {
 
     let socket_address = app_dir.join(app_info.name.clone() + ".sock");
     let pid_path = app_dir.join(app_info.name.clone() + ".pid");
+    let log_path = app_dir.join(app_info.name.clone() + ".log");
+    let failed_path = app_dir.join(app_info.name.clone() + ".failed");
+    let history_path = app_dir.join(app_info.name.clone() + ".history");
 
     drop(app_dir);
 
+    let mut cmd = app_info.cmd.clone();
+    let work_dir = app_info.work_dir();
+    let env = environment::subprocess_env(&app_info);
+
+    // The interpreter or executable is found with the PATH of the application.
+    if let Some(path) = &app_info.path {
+        if let Some(program) = environment::find_executable(&cmd[0], path) {
+            cmd[0] = program.to_string_lossy().to_string();
+        }
+    }
+    let restart_policy = app_info.start_args.restart_policy.unwrap_or_default();
+    let restart_delay = app_info.start_args.restart_delay.unwrap_or(1);
+    let max_restarts = app_info.start_args.max_restarts;
+    let crash_loop_limit = app_info.start_args.crash_loop_limit.unwrap_or(5);
+    let crash_loop_window = app_info.start_args.crash_loop_window.unwrap_or(60);
+
     info!("Starting subprocess.");
 
-    let (mut subprocess, stdin, pid) = match exec_subprocess(pid_path, app_info.cmd.clone()) {
+    let (mut subprocess, stdin, pid) = match exec_subprocess(&pid_path, &cmd, &work_dir, &env) {
         Ok(subprocess) => subprocess,
         Err(err) => {
-            error!("{err}");
+            mark_failed(&failed_path, &log_path, err.to_string());
             return Err(anyhow!("Shutting down."));
         }
     };
 
     info!("Subprocess started.");
 
+    history::append_event(
+        &history_path,
+        &HistoryEvent::Start {
+            timestamp: util::unix_timestamp(),
+            pid: pid.as_u32(),
+        },
+    );
+
     let listener = match UnixListener::bind(&socket_address) {
         Ok(socket) => socket,
         Err(err) => {
@@ -55,107 +221,193 @@ pub fn start(app_info: Application, app_dir: PathBuf) -> Result<()> {
         }
     };
 
-    let command_history = Arc::new(Mutex::new(Vec::new()));
-    let app_info = Arc::new(Mutex::new(app_info));
-
-    thread::Builder::new()
-        .name(String::from("subprocess_socket"))
-        .spawn(move || {
-            for client in listener.incoming() {
-                match client {
-                    Ok(mut stream) => {
-                        let mut stdin = stdin.try_clone().unwrap();
-                        let history = command_history.clone();
-                        let app_info = app_info.clone();
-
-                        thread::spawn(move || {
-                            let mut history = history.lock().unwrap();
-                            let app_info = app_info.lock().unwrap();
-
-                            let mut write_to_stdin = |cmd: String| {
-                                if let Err(err) = stdin.write_all(cmd.as_bytes()) {
-                                    error!("Error writing to subprocess stdin: {err}.");
-
-                                    // Should any error here shutdown and exit?
-                                    // Only exiting if the pipe is closed for now
-                                    if err.kind() == ErrorKind::BrokenPipe {
-                                        info!("Sending SIGTERM to subprocess.");
-                                        if let Err(err) = send_unix_signal(pid, 15) {
-                                            error!("{err}");
-                                        }
-                                    }
-                                }
-                            };
-
-                            let mut received = vec![0u8; 1024];
-                            let mut read: usize = 0;
-
-                            while read_socket_stream(&mut stream, &mut received, &mut read) > 0 {
-                                match serde_json::from_slice::<SocketEvent>(&received[..read]) {
-                                    Ok(message) => match message {
-                                        SocketEvent::WriteStdin(content) => {
-                                            info!("Command received: '{}'", &content);
-                                            history.insert(0, content.to_string());
-                                            let cmd = content.trim().to_owned() + "\n";
-                                            write_to_stdin(cmd)
-                                        }
-                                        SocketEvent::CommandHistory(_) => {
-                                            let event = serde_json::to_vec(
-                                                &SocketEvent::CommandHistory(history.clone()),
-                                            )
-                                            .unwrap();
-                                            stream.write_all(&event).unwrap();
-                                        }
-                                        SocketEvent::RetrieveAppInfo(_) => {
-                                            let event =
-                                                serde_json::to_vec(&app_info.clone()).unwrap();
-                                            stream.write_all(&event).unwrap();
-                                        }
-                                        SocketEvent::Ping => {
-                                            let event =
-                                                serde_json::to_vec(&SocketEvent::Ping).unwrap();
-                                            stream.write_all(&event).unwrap();
-                                        }
-
-                                        SocketEvent::Stop => {
-                                            info!("Received stop command.");
-
-                                            match app_info.stop_command.clone() {
-                                                Some(stop_command) => {
-                                                    info!("Stop command found, forwarding it.");
-                                                    write_to_stdin(stop_command)
-                                                }
-                                                None => {
-                                                    info!("Sending SIGTERM to subprocess.");
-                                                    if let Err(err) = send_unix_signal(pid, 15) {
-                                                        error!("{err}");
-                                                    }
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                    },
-                                    Err(err) => {
-                                        error!("Error converting event to struct: {err}")
-                                    }
-                                };
-                            }
-                        });
-                    }
-                    Err(err) => {
-                        error!("Socket error: {err}")
-                    }
+    // Other users can only connect if they are in the access list, which is checked for every client.
+    let mode = match app_info.access {
+        Some(_) => 0o666,
+        None => 0o600,
+    };
+
+    if let Err(err) = fs::set_permissions(&socket_address, Permissions::from_mode(mode)) {
+        error!("Error setting socket permissions: {err}.");
+    }
+
+    let state = Arc::new(Mutex::new(SubprocessState {
+        pid: Some(pid),
+        stdin: Some(Arc::new(Mutex::new(stdin))),
+        health: app_info
+            .health_check
+            .as_ref()
+            .map(|_| HealthStatus::default()),
+        ..Default::default()
+    }));
+
+    let (stop_sender, stop_receiver) = bounded(1);
+    let (stopped_sender, stopped_receiver) = bounded(1);
+    let (shutdown_sender, shutdown_receiver) = bounded(0);
+
+    // The subprocess is already running, it is stopped instead of being left unsupervised.
+    let remote_address = match start_supervision(
+        app_info,
+        log_path.clone(),
+        listener,
+        state.clone(),
+        stop_sender,
+        stopped_sender,
+        Shutdown(shutdown_receiver),
+    ) {
+        Ok(remote_address) => remote_address,
+        Err(err) => {
+            mark_failed(&failed_path, &log_path, err.to_string());
+            state.lock().unwrap().stop_requested = Some(StoppedBy::Stop);
+
+            subprocess.terminate()?;
+            if subprocess.wait_timeout(SIGKILL_TIMEOUT)?.is_none() {
+                subprocess.kill()?;
+                subprocess.wait()?;
+            }
+
+            if let Err(err) = fs::remove_file(&socket_address) {
+                error!("Error removing socket file: {err}.");
+            }
+
+            return Err(anyhow!("Shutting down."));
+        }
+    };
+
+    let mut restarts: u32 = 0;
+    let mut crash_loop = CrashLoop::new(crash_loop_limit, Duration::from_secs(crash_loop_window));
+
+    loop {
+        let started_at = Instant::now();
+
+        let status = match subprocess.wait() {
+            Ok(status) => {
+                info!("Subprocess exited with status: {:?}.", status);
+                status
+            }
+            Err(err) => {
+                error!("Error waiting: {err}.");
+                break;
+            }
+        };
+
+        let restart_requested = {
+            let mut state = state.lock().unwrap();
+            state.pid = None;
+            state.stdin = None;
+
+            let restart_requested = state.restart_requested.take();
+            let stopped_by = state.stop_requested.or(restart_requested);
+
+            let exit = ExitRecord::new(
+                util::unix_timestamp(),
+                &status,
+                started_at.elapsed().as_secs(),
+                stopped_by,
+            );
+
+            history::append_event(&history_path, &HistoryEvent::Exit(exit));
+
+            if state.stop_requested.is_some() {
+                break;
+            }
+
+            restart_requested
+        };
+
+        if restart_requested.is_none() && !restart_policy.should_restart(&status) {
+            break;
+        }
+
+        // Scheduled restarts are expected, they are not counted as crashes or delayed.
+        let delay = if restart_requested == Some(StoppedBy::ScheduledRestart) {
+            info!("Restarting subprocess.");
+            Duration::ZERO
+        } else {
+            if crash_loop.record_exit(Instant::now()) {
+                let reason = format!(
+                    "Crash loop detected, exited {crash_loop_limit} times in {crash_loop_window} seconds. Last exit status: {status:?}."
+                );
+                mark_failed(&failed_path, &log_path, reason);
+                break;
+            }
+
+            if started_at.elapsed() >= STABLE_UPTIME {
+                restarts = 0;
+            }
+
+            if let Some(max_restarts) = max_restarts {
+                if restarts >= max_restarts {
+                    let reason = format!(
+                        "Reached the maximum of {max_restarts} restarts. Last exit status: {status:?}."
+                    );
+                    mark_failed(&failed_path, &log_path, reason);
+                    break;
                 }
             }
-        })?;
 
-    match subprocess.wait() {
-        Ok(status) => {
-            info!("Subprocess exited with status: {:?}.", status);
+            let delay = get_restart_delay(restart_delay, restarts);
+            restarts += 1;
+
+            info!(
+                "Restarting subprocess in {} seconds, attempt {restarts}.",
+                delay.as_secs()
+            );
+
+            delay
+        };
+
+        // Receiving means a stop was requested while waiting.
+        if stop_receiver.recv_timeout(delay).is_ok() {
+            break;
         }
-        Err(err) => {
-            error!("Error waiting: {err}.");
+
+        let (new_subprocess, stdin, pid) = match exec_subprocess(&pid_path, &cmd, &work_dir, &env) {
+            Ok(subprocess) => subprocess,
+            Err(err) => {
+                mark_failed(&failed_path, &log_path, err.to_string());
+                break;
+            }
+        };
+
+        let mut state = state.lock().unwrap();
+
+        // A stop might have been requested right after the wait ended.
+        if state.stop_requested.is_some() {
+            drop(state);
+            let mut new_subprocess = new_subprocess;
+            new_subprocess.terminate()?;
+            new_subprocess.wait()?;
+            break;
         }
+
+        state.pid = Some(pid);
+        state.stdin = Some(Arc::new(Mutex::new(stdin)));
+        state.health = state.health.as_ref().map(|_| HealthStatus::default());
+        subprocess = new_subprocess;
+
+        history::append_event(
+            &history_path,
+            &HistoryEvent::Start {
+                timestamp: util::unix_timestamp(),
+                pid: pid.as_u32(),
+            },
+        );
+
+        info!("Subprocess restarted.");
+    }
+
+    // Giving time for the client that requested the stop to receive the result.
+    if state.lock().unwrap().stop_requested.is_some() {
+        let _ = stopped_receiver.recv_timeout(SIGKILL_TIMEOUT);
+    }
+
+    // Stops the helper threads, the listeners are woken up by connecting to them.
+    drop(shutdown_sender);
+    let _ = UnixStream::connect(&socket_address);
+
+    if let Some(address) = remote_address {
+        let _ = TcpStream::connect(address);
     }
 
     if socket_address.exists() {
@@ -175,12 +427,779 @@ pub fn start(app_info: Application, app_dir: PathBuf) -> Result<()> {
     Ok(())
 }
 
-fn exec_subprocess(pid_path: PathBuf, args: Vec<String>) -> Result<(Popen, File, Pid)> {
+// Starts the threads supervising the subprocess: health checks, schedules and the listeners,
+// returning the address of the remote listener.
+fn start_supervision(
+    app_info: Application,
+    log_path: PathBuf,
+    listener: UnixListener,
+    state: Arc<Mutex<SubprocessState>>,
+    stop_sender: Sender<()>,
+    stopped_sender: Sender<()>,
+    shutdown: Shutdown,
+) -> Result<Option<SocketAddr>> {
+    if let Some(health_check) = app_info.health_check.clone() {
+        start_health_checks(
+            health_check,
+            &app_info,
+            log_path,
+            state.clone(),
+            stop_sender.clone(),
+            stopped_sender.clone(),
+            shutdown.clone(),
+        )?;
+    }
+
+    let command_history = Arc::new(Mutex::new(Vec::new()));
+
+    if let Some(scheduled_restart) = app_info.scheduled_restart.clone() {
+        let history = command_history.clone();
+        let warning_state = state.clone();
+        let restart_state = state.clone();
+        let stop_state = state.clone();
+        let shutdown = shutdown.clone();
+        let stop_command = app_info.stop_command.clone();
+        let stop_timeout =
+            Duration::from_secs(app_info.stop_timeout.unwrap_or(DEFAULT_STOP_TIMEOUT));
+
+        schedule::start_scheduled_restart(
+            scheduled_restart,
+            move |warning| send_command(&history, &warning_state, warning),
+            move || {
+                request_restart(
+                    &restart_state,
+                    StoppedBy::ScheduledRestart,
+                    stop_command.clone(),
+                    stop_timeout,
+                )
+            },
+            move || stop_state.lock().unwrap().stop_requested.is_some() || shutdown.is_set(),
+        )?;
+    }
+
+    if let Some(schedules) = app_info.schedules.clone() {
+        let history = command_history.clone();
+        let run_state = state.clone();
+        let stop_state = state.clone();
+        let shutdown = shutdown.clone();
+
+        schedule::start_schedules(
+            schedules,
+            move |command| send_command(&history, &run_state, command),
+            move || stop_state.lock().unwrap().stop_requested.is_some() || shutdown.is_set(),
+        )?;
+    }
+
+    let clients = Clients {
+        history: command_history,
+        app_info: Arc::new(app_info),
+        state: state.clone(),
+        stop_sender,
+        stopped_sender,
+    };
+
+    start_socket_listener(listener, clients.clone(), shutdown.clone())?;
+
+    // The application keeps running without it, the error is only logged.
+    let remote_address = match &clients.app_info.remote {
+        Some(remote) => match start_remote_listener(remote, clients.clone(), shutdown) {
+            Ok(address) => Some(address),
+            Err(err) => {
+                error!("{err}");
+                None
+            }
+        },
+        None => None,
+    };
+
+    Ok(remote_address)
+}
+
+// Runs the health check on an interval while the subprocess is running,
+// running the action once the application becomes unhealthy.
+fn start_health_checks(
+    health_check: HealthCheck,
+    app_info: &Application,
+    log_path: PathBuf,
+    state: Arc<Mutex<SubprocessState>>,
+    stop_sender: Sender<()>,
+    stopped_sender: Sender<()>,
+    shutdown: Shutdown,
+) -> Result<()> {
+    let stop_command = app_info.stop_command.clone();
+    let stop_timeout = Duration::from_secs(app_info.stop_timeout.unwrap_or(DEFAULT_STOP_TIMEOUT));
+    let work_dir = app_info.work_dir();
+
+    output::spawn("health_check", move || {
+        let mut checked_pid = None;
+        let mut started_at = Instant::now();
+
+        loop {
+            if !shutdown.wait(health_check.interval()) {
+                return;
+            }
+
+            let pid = {
+                let state = state.lock().unwrap();
+
+                if state.stop_requested.is_some() {
+                    return;
+                }
+
+                match state.pid {
+                    Some(pid) => pid,
+                    None => continue,
+                }
+            };
+
+            if checked_pid != Some(pid) {
+                checked_pid = Some(pid);
+                started_at = Instant::now();
+            }
+
+            let result =
+                health_check
+                    .probe
+                    .check(health_check.timeout(), &log_path, &work_dir, |command| {
+                        if let Err(err) = write_to_stdin(&state, command) {
+                            error!("{err}");
+                        }
+                    });
+
+            if let Err(err) = &result {
+                warn!("Health check failed: {err}");
+            }
+
+            let in_start_period = started_at.elapsed() < health_check.start_period();
+
+            let action = {
+                let mut state = state.lock().unwrap();
+
+                // Restarted while checking, the result is from the previous subprocess.
+                if state.pid != Some(pid) {
+                    continue;
+                }
+
+                let health = state.health.get_or_insert_with(HealthStatus::default);
+                let timestamp = util::unix_timestamp();
+
+                if !health.record(timestamp, result, health_check.retries(), in_start_period) {
+                    continue;
+                }
+
+                let action = health_check.action.unwrap_or_default();
+
+                match action {
+                    HealthAction::None => {}
+                    HealthAction::Restart => state.restart_requested = Some(StoppedBy::HealthCheck),
+                    HealthAction::Stop => state.stop_requested = Some(StoppedBy::HealthCheck),
+                }
+
+                action
+            };
+
+            error!("Application is unhealthy, action: {action:?}.");
+
+            if action == HealthAction::None {
+                continue;
+            }
+
+            if action == HealthAction::Stop {
+                let _ = stop_sender.try_send(());
+            }
+
+            match stop_subprocess(&state, pid, stop_command.clone(), stop_timeout) {
+                Ok(step) => info!("{step}"),
+                Err(err) => error!("{err}"),
+            }
+
+            if action == HealthAction::Stop {
+                let _ = stopped_sender.try_send(());
+                return;
+            }
+        }
+    })?;
+
+    Ok(())
+}
+
+// Shared by every connection, from the application socket or the remote listener.
+#[derive(Clone)]
+struct Clients {
+    history: Arc<Mutex<Vec<String>>>,
+    // Never changes after starting, shared without a lock.
+    app_info: Arc<Application>,
+    state: Arc<Mutex<SubprocessState>>,
+    stop_sender: Sender<()>,
+    stopped_sender: Sender<()>,
+}
+
+impl Clients {
+    // Every client is handled in its own thread.
+    fn spawn<S: Read + Write + Send + 'static>(&self, stream: S, peer: Peer) {
+        let clients = self.clone();
+
+        let result = output::spawn("socket_client", move || {
+            handle_client(stream, &clients, &peer)
+        });
+
+        if let Err(err) = result {
+            error!("Error starting client thread: {err}");
+        }
+    }
+}
+
+fn start_socket_listener(
+    listener: UnixListener,
+    clients: Clients,
+    shutdown: Shutdown,
+) -> Result<()> {
+    output::spawn("subprocess_socket", move || {
+        for client in listener.incoming() {
+            if shutdown.is_set() {
+                return;
+            }
+
+            let stream = match client {
+                Ok(stream) => stream,
+                Err(err) => {
+                    error!("Socket error: {err}");
+                    continue;
+                }
+            };
+
+            // Clients without access are still accepted, their requests are denied.
+            match Peer::unix(&stream, clients.app_info.access.as_ref()) {
+                Ok(peer) => clients.spawn(stream, peer),
+                Err(err) => error!("Error reading socket client credentials: {err}"),
+            }
+        }
+    })?;
+
+    Ok(())
+}
+
+// Accepts clients that authenticate with the token before any other event,
+// returning an address the listener can be reached on.
+fn start_remote_listener(
+    remote: &RemoteListener,
+    clients: Clients,
+    shutdown: Shutdown,
+) -> Result<SocketAddr> {
+    let token = remote.token()?;
+    let listener = TcpListener::bind(&remote.listen)
+        .context(format!("Error listening on '{}'.", remote.listen))?;
+
+    let mut address = listener.local_addr()?;
+
+    // Listening on every interface, the listener is reached through the loopback one.
+    if address.ip().is_unspecified() {
+        address.set_ip(match address.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        });
+    }
+
+    info!("Listening for remote clients on '{}'.", remote.listen);
+
+    output::spawn("remote_listener", move || {
+        for client in listener.incoming() {
+            if shutdown.is_set() {
+                return;
+            }
+
+            let mut stream = match client {
+                Ok(stream) => stream,
+                Err(err) => {
+                    error!("Remote listener error: {err}");
+                    continue;
+                }
+            };
+
+            let clients = clients.clone();
+            let token = token.clone();
+
+            // Authenticating in the client thread, a slow client can't hold up the others.
+            let result = output::spawn("remote_client", move || {
+                let address = match stream.peer_addr() {
+                    Ok(address) => address.to_string(),
+                    Err(_) => String::from("unknown address"),
+                };
+
+                match authenticate(&mut stream, &token) {
+                    Ok(_) => {
+                        info!("Remote client from {address} authenticated.");
+                        handle_client(stream, &clients, &Peer::remote(&address));
+                    }
+                    Err(err) => warn!("Remote client from {address} denied: {err}"),
+                }
+            });
+
+            if let Err(err) = result {
+                error!("Error starting client thread: {err}");
+            }
+        }
+    })?;
+
+    Ok(address)
+}
+
+fn authenticate(stream: &mut TcpStream, token: &str) -> Result<()> {
+    let mut reader = remote::DeadlineReader::new(stream, remote::AUTH_TIMEOUT);
+
+    let request: Message = socket::read_event_with_limit(&mut reader, remote::AUTH_MAX_FRAME_SIZE)?
+        .ok_or(anyhow!("Connection closed before authenticating."))?;
+
+    let authenticated = match &request.event {
+        SocketEvent::Auth(received) => remote::tokens_match(received, token),
+        _ => false,
+    };
+
+    let response = match authenticated {
+        true => SocketEvent::Ack,
+        false => SocketEvent::Error(String::from("Authentication failed.")),
+    };
+
+    socket::write_event(
+        stream,
+        &Message {
+            id: request.id,
+            event: response,
+        },
+    )?;
+
+    if !authenticated {
+        return Err(anyhow!("Authentication failed."));
+    }
+
+    stream.set_read_timeout(None)?;
+    Ok(())
+}
+
+fn handle_client<S: Read + Write + Send + 'static>(mut stream: S, clients: &Clients, peer: &Peer) {
+    let history = clients.history.as_ref();
+    let app_info = clients.app_info.as_ref();
+    let state = clients.state.as_ref();
+    let stop_sender = &clients.stop_sender;
+    let stopped_sender = &clients.stopped_sender;
+
+    loop {
+        let frame = match socket::read_frame(&mut stream) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(err) => {
+                error!("Error reading from socket: {err}");
+                break;
+            }
+        };
+
+        let Message { id, event } = match serde_json::from_slice::<Message>(&frame) {
+            Ok(message) => message,
+            Err(err) => {
+                error!("Error converting event to struct: {err}");
+
+                let response = Message {
+                    id: socket::request_id(&frame),
+                    event: SocketEvent::Error(format!("Invalid request: {err}.")),
+                };
+
+                if let Err(err) = socket::write_event(&mut stream, &response) {
+                    error!("Error writing to socket: {err}");
+                    break;
+                }
+
+                continue;
+            }
+        };
+
+        if let Err(err) = peer.check(&event) {
+            warn!("Denied request from {}: {err}", peer.name);
+
+            let response = Message {
+                id,
+                event: SocketEvent::Error(err),
+            };
+
+            if let Err(err) = socket::write_event(&mut stream, &response) {
+                error!("Error writing to socket: {err}");
+                break;
+            }
+
+            continue;
+        }
+
+        let mut close = false;
+        let mut subscription = None;
+
+        let response = match event {
+            SocketEvent::WriteStdin(content) => {
+                info!("Command received: '{}'", &content);
+
+                match send_command(history, state, content) {
+                    Ok(_) => SocketEvent::Ack,
+                    Err(err) => {
+                        error!("{err}");
+                        SocketEvent::Error(err.to_string())
+                    }
+                }
+            }
+            SocketEvent::CommandHistory(_) => {
+                let history = history.lock().unwrap().clone();
+                SocketEvent::CommandHistory(history)
+            }
+            SocketEvent::RetrieveAppInfo(_) => {
+                let mut app_info = app_info.clone();
+
+                // Restarting needs the values, only admins can stop the application.
+                if peer.level != Some(AccessLevel::Admin) {
+                    environment::mask_application(&mut app_info);
+                }
+
+                SocketEvent::RetrieveAppInfo(Box::new(app_info))
+            }
+            SocketEvent::Health(_) => {
+                let health = state.lock().unwrap().health.clone();
+                SocketEvent::Health(health)
+            }
+            SocketEvent::Hello(hello) => {
+                if !hello.is_current() {
+                    info!(
+                        "Client from crescent {} (protocol {}) connected.",
+                        hello.version, hello.protocol
+                    );
+                }
+
+                SocketEvent::Hello(Hello::current())
+            }
+            SocketEvent::Ping => SocketEvent::Ping,
+            // Only remote clients have to authenticate, before any other event.
+            SocketEvent::Auth(_) => SocketEvent::Ack,
+            SocketEvent::Capture { command, capture } => {
+                info!("Command received: '{}'", &command);
+
+                match capture_command(history, state, command, &capture) {
+                    Ok((lines, matched)) => SocketEvent::Captured { lines, matched },
+                    Err(err) => {
+                        error!("{err}");
+                        SocketEvent::Error(err.to_string())
+                    }
+                }
+            }
+            SocketEvent::Subscribe => {
+                // Subscribing before answering, no line is missed.
+                subscription = Some(output::subscribe());
+                SocketEvent::Ack
+            }
+            SocketEvent::Stop {
+                force,
+                stopped_by,
+                timeout,
+            } => {
+                info!("Received stop command.");
+                close = true;
+
+                let pid = {
+                    let mut state = state.lock().unwrap();
+                    state.stop_requested = Some(stopped_by);
+                    state.pid
+                };
+
+                let _ = stop_sender.try_send(());
+
+                let timeout = timeout
+                    .or(app_info.stop_timeout)
+                    .unwrap_or(DEFAULT_STOP_TIMEOUT);
+
+                let stop_command = match &app_info.stop_command {
+                    Some(stop_command) if !force => Some(stop_command.clone()),
+                    _ => None,
+                };
+
+                if !force && stop_command.is_none() {
+                    info!("No stop command configured, sending SIGTERM.");
+                }
+
+                let result = match pid {
+                    Some(pid) => {
+                        stop_subprocess(state, pid, stop_command, Duration::from_secs(timeout))
+                    }
+                    None => Ok(StopStep::NotRunning),
+                };
+
+                match result {
+                    Ok(step) => {
+                        info!("{step}");
+                        SocketEvent::Stopped(step)
+                    }
+                    Err(err) => {
+                        error!("{err}");
+                        SocketEvent::Error(err.to_string())
+                    }
+                }
+            }
+            SocketEvent::Stopped(_)
+            | SocketEvent::Output(_)
+            | SocketEvent::Captured { .. }
+            | SocketEvent::Ack
+            | SocketEvent::Error(_) => {
+                SocketEvent::Error(String::from("Event is only sent as a response."))
+            }
+        };
+
+        let written = socket::write_event(
+            &mut stream,
+            &Message {
+                id,
+                event: response,
+            },
+        );
+
+        // After a stop the connection is closed, the client might not be waiting for the result.
+        if close {
+            let _ = stopped_sender.try_send(());
+            break;
+        }
+
+        if let Err(err) = written {
+            error!("Error writing to socket: {err}");
+            break;
+        }
+
+        // A subscribed connection only receives the output from now on.
+        if let Some(receiver) = subscription {
+            if let Err(err) = forward_output(stream, id, receiver) {
+                error!("Error forwarding output: {err}");
+            }
+
+            break;
+        }
+    }
+}
+
+// Sends every line from the receiver to the client with the ID of its `Subscribe` request.
+fn forward_output<S: Write + Send + 'static>(
+    mut stream: S,
+    id: u64,
+    receiver: Receiver<(Source, String)>,
+) -> Result<()> {
+    output::spawn("subscriber", move || {
+        for (_, line) in receiver {
+            let message = Message {
+                id,
+                event: SocketEvent::Output(line),
+            };
+
+            if socket::write_event(&mut stream, &message).is_err() {
+                return;
+            }
+        }
+
+        // Otherwise the channel is only closed when this client could not keep up.
+        if output::is_closed() {
+            return;
+        }
+
+        let message = Message {
+            id,
+            event: SocketEvent::Error(String::from(
+                "Too far behind the application output, subscription closed.",
+            )),
+        };
+
+        let _ = socket::write_event(&mut stream, &message);
+    })?;
+
+    Ok(())
+}
+
+// Gracefully stops the subprocess so the main loop starts it again with the same command.
+fn request_restart(
+    state: &Mutex<SubprocessState>,
+    requested_by: StoppedBy,
+    stop_command: Option<String>,
+    timeout: Duration,
+) {
+    let pid = {
+        let mut state = state.lock().unwrap();
+
+        let pid = match state.pid {
+            Some(pid) if state.stop_requested.is_none() => pid,
+            _ => {
+                info!("Subprocess is not running, skipping restart.");
+                return;
+            }
+        };
+
+        state.restart_requested = Some(requested_by);
+        pid
+    };
+
+    match stop_subprocess(state, pid, stop_command, timeout) {
+        Ok(step) => info!("{step}"),
+        Err(err) => error!("{err}"),
+    }
+}
+
+// Records the command in the history and writes it to the subprocess stdin.
+fn send_command(
+    history: &Mutex<Vec<String>>,
+    state: &Mutex<SubprocessState>,
+    content: String,
+) -> Result<()> {
+    history.lock().unwrap().insert(0, content.clone());
+    let cmd = content.trim().to_owned() + "\n";
+    write_to_stdin(state, cmd)
+}
+
+// Sends the command and collects the subprocess output written after it.
+fn capture_command(
+    history: &Mutex<Vec<String>>,
+    state: &Mutex<SubprocessState>,
+    command: String,
+    capture: &Capture,
+) -> Result<(Vec<String>, bool)> {
+    capture.validate()?;
+
+    // Subscribing before writing, the first lines of the response are not missed.
+    let receiver = output::subscribe();
+    send_command(history, state, command)?;
+
+    capture.collect(&receiver)
+}
+
+fn write_to_stdin(state: &Mutex<SubprocessState>, cmd: String) -> Result<()> {
+    let (pid, stdin) = {
+        let state = state.lock().unwrap();
+
+        let pid = match state.pid {
+            Some(pid) => pid,
+            None => return Err(anyhow!("Subprocess is not running.")),
+        };
+
+        match &state.stdin {
+            Some(stdin) => (pid, stdin.clone()),
+            None => return Err(anyhow!("Subprocess stdin is not available.")),
+        }
+    };
+
+    let result = stdin.lock().unwrap().write_all(cmd.as_bytes());
+
+    if let Err(err) = result {
+        // Should any error here shutdown and exit?
+        // Only exiting if the pipe is closed for now
+        if err.kind() == ErrorKind::BrokenPipe {
+            info!("Sending SIGTERM to subprocess.");
+            if let Err(err) = send_unix_signal(pid, 15) {
+                error!("{err}");
+            }
+        }
+
+        return Err(anyhow!("Error writing to subprocess stdin: {err}."));
+    }
+
+    Ok(())
+}
+
+// Sends the stop command, if any, and escalates to SIGTERM and SIGKILL
+// every time the subprocess does not exit before the timeout.
+fn stop_subprocess(
+    state: &Mutex<SubprocessState>,
+    pid: Pid,
+    stop_command: Option<String>,
+    timeout: Duration,
+) -> Result<StopStep> {
+    if let Some(stop_command) = stop_command {
+        info!("Stop command found, forwarding it.");
+        if let Err(err) = write_to_stdin(state, stop_command) {
+            error!("{err}");
+        }
+
+        if wait_subprocess_exit(state, pid, timeout) {
+            return Ok(StopStep::StopCommand);
+        }
+
+        info!("Subprocess did not exit after the stop command.");
+    }
+
+    info!("Sending SIGTERM to subprocess.");
+    if let Err(err) = send_unix_signal(pid, 15) {
+        error!("{err}");
+    }
+
+    if wait_subprocess_exit(state, pid, timeout) {
+        return Ok(StopStep::Sigterm);
+    }
+
+    info!("Subprocess did not exit after SIGTERM, sending SIGKILL.");
+    send_unix_signal(pid, 9)?;
+
+    if wait_subprocess_exit(state, pid, SIGKILL_TIMEOUT) {
+        return Ok(StopStep::Sigkill);
+    }
+
+    Err(anyhow!("Subprocess did not exit after SIGKILL."))
+}
+
+// The main thread clears the PID as soon as the subprocess exits.
+fn wait_subprocess_exit(state: &Mutex<SubprocessState>, pid: Pid, timeout: Duration) -> bool {
+    let start = Instant::now();
+
+    loop {
+        if state.lock().unwrap().pid != Some(pid) {
+            return true;
+        }
+
+        if start.elapsed() >= timeout {
+            return false;
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// Saves the reason and the last lines from the log for `status` and `list`.
+fn mark_failed(failed_path: &Path, log_path: &Path, reason: String) {
+    error!("Marking application as failed: {reason}");
+
+    let last_lines = match Tail::new(log_path.to_path_buf()) {
+        Ok(mut log) => log.read_lines(FAILED_LOG_LINES).unwrap_or_default(),
+        Err(_) => vec![],
+    };
+
+    let state = FailedState {
+        reason,
+        failed_at: util::unix_timestamp(),
+        last_lines,
+    };
+
+    let result = File::create(failed_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| Ok(serde_json::to_writer_pretty(file, &state)?));
+
+    if let Err(err) = result {
+        error!("Error writing failed state: {err}.");
+    }
+}
+
+fn get_restart_delay(base: u64, attempt: u32) -> Duration {
+    let delay = base.saturating_mul(2u64.saturating_pow(attempt));
+    Duration::from_secs(delay.min(MAX_RESTART_DELAY))
+}
+
+fn exec_subprocess(
+    pid_path: &PathBuf,
+    args: &[String],
+    work_dir: &Path,
+    env: &Option<Vec<(OsString, OsString)>>,
+) -> Result<(Popen, File, Pid)> {
     let mut subprocess = match Popen::create(
-        &args,
+        args,
         PopenConfig {
             stdout: Redirection::Merge,
+            stderr: Redirection::Pipe,
             stdin: Redirection::Pipe,
+            cwd: Some(work_dir.into()),
+            env: env.clone(),
             ..Default::default()
         },
     ) {
@@ -188,6 +1207,11 @@ fn exec_subprocess(pid_path: PathBuf, args: Vec<String>) -> Result<(Popen, File,
         Err(err) => return Err(anyhow!("Error starting subprocess: {err}.")),
     };
 
+    // The output is read by the supervisor so it can be sent to subscribers.
+    if let Some(output) = subprocess.stderr.take() {
+        output::forward(output)?;
+    }
+
     if let Some(status) = subprocess.poll() {
         return Err(anyhow!(
             "Checked if subprocess was running and it returned status: {status:?}."
@@ -206,29 +1230,21 @@ fn exec_subprocess(pid_path: PathBuf, args: Vec<String>) -> Result<(Popen, File,
 
     let pid = subprocess.pid().expect("pid shouldn't be empty");
 
-    if let Err(err) = append_pid(&pid_path, &pid) {
+    if let Err(err) = write_pid(pid_path, &pid) {
         subprocess.terminate()?;
-        return Err(anyhow!("Error appending PID to file: {err}."));
+        return Err(anyhow!("Error writing PID to file: {err}."));
     };
 
     Ok((subprocess, stdin, Pid::from(pid as usize)))
 }
 
-fn append_pid(pid_path: &PathBuf, pid: &u32) -> Result<()> {
-    let mut pid_file = fs::OpenOptions::new()
-        .read(true)
-        .append(true)
-        .open(pid_path)?;
-    pid_file.write_all(pid.to_string().as_bytes())?;
+// The first line is always the crescent PID, the second one is replaced on every restart.
+fn write_pid(pid_path: &PathBuf, pid: &u32) -> Result<()> {
+    let pids = format!("{}\n{}", process::id(), pid);
+    fs::write(pid_path, pids)?;
     Ok(())
 }
 
-// https://codereview.stackexchange.com/questions/243693/rust-multi-cliented-tcp-server-library
-pub fn read_socket_stream(stream: &mut UnixStream, received: &mut [u8], read: &mut usize) -> usize {
-    *read = stream.read(received).unwrap_or(0);
-    *read
-}
-
 pub fn send_unix_signal(pid: Pid, signal: u8) -> Result<()> {
     let subprocess_pid: usize = pid.into();
 
@@ -260,7 +1276,9 @@ pub fn send_unix_signal(pid: Pid, signal: u8) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::access::AccessLevel;
     use crate::application::app_pids_by_name;
+    use std::os::unix::net::UnixStream;
     extern crate test_utils;
 
     #[test]
@@ -274,4 +1292,204 @@ mod tests {
         test_utils::delete_app_folder(name)?;
         Ok(())
     }
+
+    #[test]
+    fn unit_crash_loop_record_exit() {
+        let mut crash_loop = CrashLoop::new(3, Duration::from_secs(10));
+        let now = Instant::now();
+
+        assert!(!crash_loop.record_exit(now));
+        assert!(!crash_loop.record_exit(now + Duration::from_secs(1)));
+        // The first exit is outside of the window.
+        assert!(!crash_loop.record_exit(now + Duration::from_secs(11)));
+        assert!(!crash_loop.record_exit(now + Duration::from_secs(12)));
+        assert!(crash_loop.record_exit(now + Duration::from_secs(13)));
+    }
+
+    #[test]
+    fn unit_get_restart_delay() {
+        assert_eq!(get_restart_delay(1, 0), Duration::from_secs(1));
+        assert_eq!(get_restart_delay(1, 3), Duration::from_secs(8));
+        assert_eq!(get_restart_delay(5, 2), Duration::from_secs(20));
+        assert_eq!(
+            get_restart_delay(1, 20),
+            Duration::from_secs(MAX_RESTART_DELAY)
+        );
+        assert_eq!(get_restart_delay(0, 4), Duration::from_secs(0));
+    }
+
+    fn test_clients() -> Clients {
+        let (stop_sender, _) = bounded(1);
+        let (stopped_sender, _) = bounded(1);
+
+        Clients {
+            history: Arc::new(Mutex::new(vec![])),
+            app_info: Arc::new(Application::default()),
+            state: Arc::new(Mutex::new(SubprocessState::default())),
+            stop_sender,
+            stopped_sender,
+        }
+    }
+
+    #[test]
+    fn unit_handle_client_responses() -> Result<()> {
+        let (mut client, server) = UnixStream::pair()?;
+
+        let clients = test_clients();
+        let peer = Peer {
+            name: String::from("test"),
+            level: Some(AccessLevel::Admin),
+        };
+        thread::spawn(move || handle_client(server, &clients, &peer));
+
+        let ping = Message::new(SocketEvent::Ping);
+        socket::write_event(&mut client, &ping)?;
+        let response: Message = socket::read_event(&mut client)?.unwrap();
+        assert_eq!(response.id, ping.id);
+        assert!(matches!(response.event, SocketEvent::Ping));
+
+        let write = Message::new(SocketEvent::WriteStdin(String::from("ping")));
+        socket::write_event(&mut client, &write)?;
+        let response: Message = socket::read_event(&mut client)?.unwrap();
+        assert_eq!(response.id, write.id);
+        assert!(
+            matches!(response.event, SocketEvent::Error(err) if err == "Subprocess is not running.")
+        );
+
+        // Events from a newer version are answered with the ID of the request.
+        socket::write_event(
+            &mut client,
+            &serde_json::json!({"id": 42, "event": "DoesNotExist"}),
+        )?;
+        let response: Message = socket::read_event(&mut client)?.unwrap();
+        assert_eq!(response.id, 42);
+        assert!(matches!(response.event, SocketEvent::Error(_)));
+
+        socket::write_event(&mut client, &"not a message")?;
+        let response: Message = socket::read_event(&mut client)?.unwrap();
+        assert_eq!(response.id, socket::UNKNOWN_REQUEST_ID);
+        assert!(matches!(response.event, SocketEvent::Error(_)));
+
+        let (mut client, server) = UnixStream::pair()?;
+
+        let clients = test_clients();
+        let peer = Peer {
+            name: String::from("uid 1001 (gid 1001)"),
+            level: Some(AccessLevel::ReadOnly),
+        };
+        thread::spawn(move || handle_client(server, &clients, &peer));
+
+        let write = Message::new(SocketEvent::WriteStdin(String::from("op Steve")));
+        socket::write_event(&mut client, &write)?;
+        let response: Message = socket::read_event(&mut client)?.unwrap();
+        assert_eq!(response.id, write.id);
+        assert!(matches!(
+            response.event,
+            SocketEvent::Error(err) if err == "Permission denied, 'send' requires operator access."
+        ));
+
+        let history = Message::new(SocketEvent::CommandHistory(vec![]));
+        socket::write_event(&mut client, &history)?;
+        let response: Message = socket::read_event(&mut client)?.unwrap();
+        assert!(matches!(response.event, SocketEvent::CommandHistory(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn unit_socket_listener_concurrent_clients() -> Result<()> {
+        let socket_path = std::env::temp_dir().join("crescent_concurrent_clients.sock");
+
+        if socket_path.exists() {
+            fs::remove_file(&socket_path)?;
+        }
+
+        let (_shutdown_sender, shutdown) = bounded(0);
+        start_socket_listener(
+            UnixListener::bind(&socket_path)?,
+            test_clients(),
+            Shutdown(shutdown),
+        )?;
+
+        let request = |stream: &mut UnixStream, event: SocketEvent| -> Result<SocketEvent> {
+            stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+            socket::write_event(stream, &Message::new(event))?;
+            let message: Message = socket::read_event(stream)?.unwrap();
+            Ok(message.event)
+        };
+
+        // Connections kept open, like `attach` and `log --follow`.
+        let mut attached = vec![];
+
+        for _ in 0..3 {
+            let mut stream = UnixStream::connect(&socket_path)?;
+            request(&mut stream, SocketEvent::CommandHistory(vec![]))?;
+            attached.push(stream);
+
+            let mut stream = UnixStream::connect(&socket_path)?;
+            assert!(matches!(
+                request(&mut stream, SocketEvent::Subscribe)?,
+                SocketEvent::Ack
+            ));
+            attached.push(stream);
+        }
+
+        let mut stream = UnixStream::connect(&socket_path)?;
+        assert!(matches!(
+            request(&mut stream, SocketEvent::Ping)?,
+            SocketEvent::Ping
+        ));
+        assert!(matches!(
+            request(&mut stream, SocketEvent::RetrieveAppInfo(Box::default()))?,
+            SocketEvent::RetrieveAppInfo(_)
+        ));
+
+        fs::remove_file(&socket_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unit_remote_listener_authentication() -> Result<()> {
+        let token_file = std::env::temp_dir().join("crescent_remote_listener_token");
+        fs::write(&token_file, "0123456789abcdef")?;
+
+        let listener = RemoteListener {
+            listen: String::from("127.0.0.1:47801"),
+            token_file: token_file.clone(),
+            allow_public: false,
+        };
+
+        let (shutdown_sender, shutdown) = bounded(0);
+        let address = start_remote_listener(&listener, test_clients(), Shutdown(shutdown))?;
+
+        let remote = remote::Remote {
+            address: listener.listen.clone(),
+            token: String::from("fedcba9876543210"),
+        };
+
+        let err = remote.connect().unwrap_err();
+        assert_eq!(format!("{}", err), "Authentication failed.");
+
+        let remote = remote::Remote {
+            token: String::from("0123456789abcdef"),
+            ..remote
+        };
+
+        let mut stream = remote.connect()?;
+        socket::write_event(&mut stream, &Message::new(SocketEvent::Ping))?;
+        let message: Message = socket::read_event(&mut stream)?.unwrap();
+        assert!(matches!(message.event, SocketEvent::Ping));
+
+        // The port is released once the supervisor shuts down.
+        drop(shutdown_sender);
+        TcpStream::connect(address)?;
+
+        let released = (0..20).any(|_| {
+            thread::sleep(Duration::from_millis(100));
+            TcpListener::bind(&listener.listen).is_ok()
+        });
+        assert!(released);
+
+        fs::remove_file(token_file)?;
+        Ok(())
+    }
 }
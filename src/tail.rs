@@ -4,14 +4,11 @@ use std::{
     path::PathBuf,
 };
 
-use anyhow::{anyhow, Result};
-use crossbeam::channel::{unbounded, Sender};
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use anyhow::Result;
 
 pub struct Tail {
     pub file: File,
     pub length: usize,
-    pub path: PathBuf,
 }
 
 impl Tail {
@@ -23,7 +20,6 @@ impl Tail {
         Ok(Tail {
             file: log_file,
             length: metadata.len() as usize,
-            path: file_path,
         })
     }
 
@@ -51,39 +47,6 @@ impl Tail {
 
         Ok(content)
     }
-
-    pub fn watch(self, event_sender: &Sender<String>) -> Result<()> {
-        let (sender, receiver) = unbounded();
-
-        let mut watcher = RecommendedWatcher::new(sender, Config::default())?;
-
-        watcher.watch(&self.path, RecursiveMode::NonRecursive)?;
-
-        let mut content = String::new();
-        let mut cursor = self.length as u64;
-
-        for message in receiver {
-            match message {
-                Ok(event) => {
-                    if event.kind.is_modify() {
-                        let mut file = File::open(&self.path)?;
-                        file.seek(SeekFrom::Start(cursor))?;
-
-                        if file.metadata()?.len() > self.length as u64 {
-                            cursor = file.metadata()?.len();
-
-                            content.clear();
-                            file.read_to_string(&mut content)?;
-                            event_sender.send(content.clone())?;
-                        }
-                    }
-                }
-                Err(err) => return Err(anyhow!("{err}")),
-            }
-        }
-
-        Ok(())
-    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,11 @@
-use std::{env, path::PathBuf, process::Command};
-
+use std::{
+    env,
+    path::PathBuf,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Local, TimeZone, Utc};
 use crossterm::style::Stylize;
 
 pub fn get_uptime_from_seconds(secs: u64) -> String {
@@ -33,6 +39,22 @@ pub fn get_uptime_from_seconds(secs: u64) -> String {
     format!("{days} {}", check_plural(days, "day"))
 }
 
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn format_timestamp(secs: u64) -> String {
+    match Utc.timestamp_opt(secs as i64, 0).single() {
+        Some(utc) => DateTime::<Local>::from(utc)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        None => String::from("N/A"),
+    }
+}
+
 pub fn print_title_cyan(title: &str) {
     println!("{}", title.bold().cyan())
 }
@@ -28,6 +28,7 @@ pub fn start_long_running_service(name: &str) -> Result<()> {
         "python3",
         "-n",
         name,
+        "--wait",
     ];
 
     cmd.args(args);
@@ -36,9 +37,6 @@ pub fn start_long_running_service(name: &str) -> Result<()> {
         .success()
         .stderr(predicate::str::contains("Starting"));
 
-    // Sleeping to make sure the process started
-    thread::sleep(std::time::Duration::from_secs(1));
-
     Ok(())
 }
 
@@ -93,6 +91,24 @@ pub fn delete_app_folder(name: &str) -> Result<()> {
     Ok(())
 }
 
+// Writes a profile for the long running service with the extra fields, which can't be empty.
+pub fn write_test_profile(name: &str, fields: &str) -> Result<PathBuf> {
+    let home = env::var("HOME").context("Error getting HOME env.")?;
+    let profile_path = PathBuf::from(home).join(format!(".crescent/profiles/{name}.json"));
+
+    let profile = format!(
+        r#"{{
+            "file_path": "./tools/long_running_service.py",
+            "interpreter": "python3",
+            "stop_command": "stop\n",
+            {fields}
+        }}"#
+    );
+
+    fs::write(&profile_path, profile)?;
+    Ok(profile_path)
+}
+
 // Solves issues with assert_cmd not finding the binary when using cross.
 // https://github.com/assert-rs/assert_cmd/issues/139#issuecomment-1200146157
 pub fn get_base_command() -> Command {
@@ -1,7 +1,17 @@
-use std::{env, fs::File, io::Read, path::PathBuf, str::from_utf8, thread};
+use std::{
+    env,
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    os::unix::{fs::PermissionsExt, net::UnixStream},
+    path::PathBuf,
+    str::from_utf8,
+    thread,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Context, Result};
-use predicates::{prelude::predicate, Predicate};
+use predicates::{boolean::PredicateBooleanExt, prelude::predicate, Predicate};
 
 #[test]
 fn stop_force_long_running_service() -> Result<()> {
@@ -38,10 +48,8 @@ fn stop_long_running_service() -> Result<()> {
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains("Stop command sent."));
-
-    // Sleeping to make sure the process shutdown
-    thread::sleep(std::time::Duration::from_secs(1));
+        .stdout(predicate::str::contains("Stop command sent."))
+        .stdout(predicate::str::contains("stopped by the stop command"));
 
     test_utils::delete_app_folder(name)?;
     Ok(())
@@ -167,3 +175,947 @@ fn send_command_socket() -> Result<()> {
     test_utils::delete_app_folder(name)?;
     Ok(())
 }
+
+#[test]
+fn restart_long_running_service() -> Result<()> {
+    let name = "restart_long_running_service";
+    test_utils::start_long_running_service(name)?;
+    assert!(test_utils::check_app_is_running(name)?);
+
+    let home = env::var("HOME").context("Error getting HOME env.")?;
+    let pid_path = PathBuf::from(home)
+        .join(".crescent/apps/restart_long_running_service/restart_long_running_service.pid");
+
+    let mut old_pids = String::new();
+    File::open(&pid_path)?.read_to_string(&mut old_pids)?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["restart", name]);
+
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Starting"));
+
+    // Sleeping to make sure the process started
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    assert!(test_utils::check_app_is_running(name)?);
+
+    let mut new_pids = String::new();
+    File::open(&pid_path)?.read_to_string(&mut new_pids)?;
+    assert_ne!(old_pids, new_pids);
+
+    test_utils::shutdown_long_running_service(name)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn restart_profile_long_running_service() -> Result<()> {
+    let name = "restart_profile_long_running";
+    let profile_path = test_utils::write_test_profile(name, r#""stop_timeout": 10"#)?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name, "-a", "extra", "--wait"])
+        .args(["--restart", "on-failure", "--label", "restart-profile"]);
+    cmd.assert().success();
+
+    // The profile changed since the start, the command line arguments still take precedence.
+    test_utils::write_test_profile(name, r#""restart_policy": "never""#)?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["restart", name, "--profile", name]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Starting"));
+
+    // Sleeping to make sure the process started
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["status", name]);
+    cmd.assert().success().stdout(
+        predicate::str::contains(": extra\n")
+            .and(predicate::str::contains(": OnFailure\n"))
+            .and(predicate::str::contains(": restart-profile\n")),
+    );
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    std::fs::remove_file(profile_path)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn restart_policy_long_running_service() -> Result<()> {
+    let name = "restart_policy_long_running_service";
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args([
+        "start",
+        "./tools/long_running_service.py",
+        "-i",
+        "python3",
+        "-n",
+        name,
+        "--restart",
+        "always",
+        "--restart-delay",
+        "0",
+    ]);
+    cmd.assert().success();
+
+    // Sleeping to make sure the process started
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    assert!(test_utils::check_app_is_running(name)?);
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["kill", name]);
+    cmd.assert().success();
+
+    // Sleeping to make sure the process restarted
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    assert!(test_utils::check_app_is_running(name)?);
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name, "-f"]);
+    cmd.assert().success();
+
+    // Sleeping to make sure the process shutdown
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["status", name]);
+    cmd.assert().failure();
+
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn stop_timeout_escalates_to_sigkill() -> Result<()> {
+    let name = "stop_timeout_escalates_to_sigkill";
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args([
+        "start",
+        "/bin/sh",
+        "-n",
+        name,
+        "-a",
+        "-c",
+        "-a",
+        "trap '' TERM; while true; do sleep 1; done",
+    ]);
+    cmd.assert().success();
+
+    // Sleeping to make sure the process started
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name, "--timeout", "1"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Subprocess stopped by SIGKILL."));
+
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn bulk_glob_long_running_services() -> Result<()> {
+    let names = ["bulk_glob_service_a", "bulk_glob_service_b"];
+
+    for name in names {
+        test_utils::start_long_running_service(name)?;
+        assert!(test_utils::check_app_is_running(name)?);
+    }
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["send", "bulk_glob_service_a,bulk_glob_service_b", "ping"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Results"))
+        .stdout(predicate::str::contains("Command sent.").count(2));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["signal", "bulk_glob_service_*", "15"]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Signal sent.").count(2))
+        .stdout(predicate::str::contains("applications failed").not());
+
+    // Sleeping to make sure the processes exited
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    for name in names {
+        test_utils::delete_app_folder(name)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn health_check_stdin_long_running_service() -> Result<()> {
+    let name = "health_check_stdin";
+    let profile_path = test_utils::write_test_profile(
+        name,
+        r#""health_check": {"type": "stdin", "command": "ping", "expect": "^pog$", "interval": 1}"#,
+    )?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name]);
+    cmd.assert().success();
+
+    // Waiting for at least one check to run
+    thread::sleep(std::time::Duration::from_secs(3));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["status", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Healthy"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["list"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Healthy"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    std::fs::remove_file(profile_path)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn health_check_action_stop_long_running_service() -> Result<()> {
+    let name = "health_check_action_stop";
+    let profile_path = test_utils::write_test_profile(
+        name,
+        r#""health_check": {"type": "tcp", "port": 1, "interval": 1, "retries": 2, "action": "stop"}"#,
+    )?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name]);
+    cmd.assert().success();
+
+    // Two failed checks and the stop command
+    thread::sleep(std::time::Duration::from_secs(4));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["history", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("health check"));
+
+    std::fs::remove_file(profile_path)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn start_wait_readiness_long_running_service() -> Result<()> {
+    let name = "start_wait_readiness";
+    let profile_path = test_utils::write_test_profile(
+        name,
+        r#""readiness": {"type": "log_line", "regex": "^Started long running service$"}"#,
+    )?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name, "--wait", "--timeout", "10"]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("log line matching"))
+        .stdout(predicate::str::contains("Application ready."));
+
+    assert!(test_utils::check_app_is_running(name)?);
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    std::fs::remove_file(profile_path)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn schedules_long_running_service() -> Result<()> {
+    let name = "schedules_long_running";
+    let profile_path =
+        test_utils::write_test_profile(name, r#""schedules": {"* * * * * *": "ping"}"#)?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name, "--wait"]);
+    cmd.assert().success();
+
+    // Waiting for the schedule to run at least once
+    thread::sleep(std::time::Duration::from_secs(2));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["status", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("'ping', next run at"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["log", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Running scheduled command: 'ping'",
+        ))
+        .stdout(predicate::str::contains("pog"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    std::fs::remove_file(profile_path)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn scheduled_restart_long_running_service() -> Result<()> {
+    let name = "scheduled_restart_long_running";
+    let profile_path = test_utils::write_test_profile(
+        name,
+        r#""scheduled_restart": {"cron": "*/4 * * * * *", "warnings": ["2s"], "warning_command": "say Restarting in {remaining}"}"#,
+    )?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name, "--wait"]);
+    cmd.assert().success();
+
+    // Waiting for the warning and at least one restart
+    thread::sleep(std::time::Duration::from_secs(6));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["log", name]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Sending restart warning: 'say Restarting in 2 seconds'",
+    ));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["history", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("scheduled restart"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    std::fs::remove_file(profile_path)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn status_long_command_line_long_running_service() -> Result<()> {
+    let name = "status_long_command_line";
+    let arguments = "-XX:+UseG1GC ".repeat(200);
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args([
+        "start",
+        "./tools/long_running_service.py",
+        "-i",
+        "python3",
+        "-n",
+        name,
+        "-a",
+        &arguments,
+        "--wait",
+    ]);
+    cmd.assert().success();
+
+    // The application information is bigger than a single read from the socket.
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["status", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(arguments.trim()))
+        .stdout(predicate::str::contains(env!("CARGO_PKG_VERSION")));
+
+    test_utils::shutdown_long_running_service(name)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn log_follow_long_running_service() -> Result<()> {
+    let name = "log_follow_long_running";
+    test_utils::start_long_running_service(name)?;
+
+    let follow = std::process::Command::new(env!("CARGO_BIN_EXE_cres"))
+        .args(["log", name, "--follow"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+
+    // Waiting for the subscription
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["send", name, "ping"]);
+    cmd.assert().success();
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    // The connection is closed when the supervisor exits.
+    let output = follow.wait_with_output()?;
+    let stdout = from_utf8(&output.stdout)?;
+
+    assert!(stdout.contains(">> Watching log"));
+    assert!(stdout.contains("pog"));
+    assert!(stdout.contains("Received stop command."));
+    assert!(stdout.ends_with(">> Application stopped\n"));
+
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn send_wait_long_running_service() -> Result<()> {
+    let name = "send_wait_long_running";
+    test_utils::start_long_running_service(name)?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["send", "--wait", "--until", "^pog$", name, "ping"]);
+    cmd.assert().success().stdout(predicate::str::diff("pog\n"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["send", "-w", "--lines", "1", name, "ping"]);
+    cmd.assert().success().stdout(predicate::str::diff("pog\n"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["send", "-w", "--until", "pong", "--idle-timeout", "1"]);
+    cmd.args([name, "ping"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("pog"))
+        .stderr(predicate::str::contains("No output matched 'pong'."));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn remote_long_running_service() -> Result<()> {
+    let name = "remote_long_running";
+    let token_file = env::temp_dir().join("crescent_remote_long_running_token");
+    std::fs::write(&token_file, "0123456789abcdef")?;
+
+    // The listener is not encrypted, other addresses than loopback have to be allowed.
+    test_utils::write_test_profile(
+        name,
+        &format!(
+            r#""remote": {{"listen": "0.0.0.0:47802", "token_file": "{}"}}"#,
+            token_file.display()
+        ),
+    )?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("set 'allow_public'"));
+
+    let profile_path = test_utils::write_test_profile(
+        name,
+        &format!(
+            r#""remote": {{"listen": "127.0.0.1:47802", "token_file": "{}"}}"#,
+            token_file.display()
+        ),
+    )?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name, "--wait"]);
+    cmd.assert().success();
+
+    let remote = ["--remote", "127.0.0.1:47802"];
+
+    // A client that never authenticates doesn't hold up the others.
+    let _idle = TcpStream::connect("127.0.0.1:47802")?;
+
+    // Frames larger than a token are rejected before authenticating.
+    let mut oversized = TcpStream::connect("127.0.0.1:47802")?;
+    oversized.write_all(&(1024 * 1024u32).to_be_bytes())?;
+    oversized.set_read_timeout(Some(Duration::from_secs(5)))?;
+    assert!(matches!(oversized.read(&mut [0u8; 1]), Ok(0) | Err(_)));
+
+    let started = Instant::now();
+    let mut cmd = test_utils::get_base_command();
+    cmd.env("CRESCENT_TOKEN", "0123456789abcdef");
+    cmd.args(remote).args(["status", name]);
+    cmd.assert().success();
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.env("CRESCENT_TOKEN", "fedcba9876543210");
+    cmd.args(remote).args(["status", name]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Authentication failed."));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(remote)
+        .args(["--token-file", token_file.to_str().unwrap()]);
+    cmd.args(["status", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("127.0.0.1:47802"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.env("CRESCENT_TOKEN", "0123456789abcdef");
+    cmd.args(remote).arg("list");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(name));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.env("CRESCENT_TOKEN", "0123456789abcdef");
+    cmd.args(remote)
+        .args(["send", "-w", "--until", "pog", name, "ping"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("pog"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.env("CRESCENT_TOKEN", "0123456789abcdef");
+    cmd.args(remote).args(["send", "not_this_app", "ping"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Application does not exist."));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.env("CRESCENT_TOKEN", "0123456789abcdef");
+    cmd.args(remote).args(["stop", name]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Subprocess stopped by the stop command.",
+    ));
+
+    std::fs::remove_file(profile_path)?;
+    std::fs::remove_file(token_file)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+const API_TOKEN: &str = "0123456789abcdef";
+
+// Sends a request to the API over its Unix socket, returning the status code and body.
+fn api_request(socket: &PathBuf, method: &str, path: &str, body: &str) -> Result<(u16, String)> {
+    let headers = format!(
+        "Host: localhost\r\nAuthorization: Bearer {API_TOKEN}\r\nContent-Type: application/json\r\n"
+    );
+
+    api_request_with_headers(socket, method, path, &headers, body)
+}
+
+fn api_request_with_headers(
+    socket: &PathBuf,
+    method: &str,
+    path: &str,
+    headers: &str,
+    body: &str,
+) -> Result<(u16, String)> {
+    let mut stream = UnixStream::connect(socket)?;
+
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\n{headers}Connection: close\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .context("Response without a body.")?;
+
+    let status = head
+        .split(' ')
+        .nth(1)
+        .context("Response without a status.")?
+        .parse()?;
+
+    Ok((status, body.to_string()))
+}
+
+#[test]
+fn api_long_running_service() -> Result<()> {
+    let name = "api_long_running";
+    let socket = env::temp_dir().join("crescent_api_long_running.sock");
+    let profile_path = test_utils::write_test_profile(name, r#""stop_timeout": 10"#)?;
+    let token_file = env::temp_dir().join("crescent_api_long_running_token");
+    std::fs::write(&token_file, API_TOKEN)?;
+
+    let mut api = std::process::Command::new(env!("CARGO_BIN_EXE_cres"))
+        .args(["api", "--socket", socket.to_str().unwrap()])
+        .args(["--auth-token-file", token_file.to_str().unwrap()])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+
+    while !socket.exists() {
+        thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    // Set right after binding, the API server could still be starting.
+    thread::sleep(std::time::Duration::from_millis(200));
+    let mode = std::fs::metadata(&socket)?.permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let json = "Host: localhost\r\nContent-Type: application/json\r\n";
+    let (status, _) = api_request_with_headers(&socket, "GET", "/apps", json, "")?;
+    assert_eq!(status, 401);
+
+    let auth = format!("Authorization: Bearer {API_TOKEN}\r\n");
+
+    // Pages rebinding their name to the loopback address send it as the Host.
+    let headers = format!("Host: attacker.example:7600\r\n{auth}");
+    let (status, _) = api_request_with_headers(&socket, "GET", "/apps", &headers, "")?;
+    assert_eq!(status, 403);
+
+    // Forms can only send 'text/plain' and other simple content types.
+    let headers = format!("Host: localhost\r\n{auth}Content-Type: text/plain\r\n");
+    let (status, body) =
+        api_request_with_headers(&socket, "POST", &format!("/apps/{name}/stop"), &headers, "")?;
+    assert_eq!(status, 415);
+    assert!(body.contains("Content-Type has to be 'application/json'."));
+
+    let (status, body) = api_request(
+        &socket,
+        "POST",
+        "/apps",
+        &format!(r#"{{"profile": "{name}", "name": "{name}"}}"#),
+    )?;
+    assert_eq!(status, 200, "{body}");
+
+    let (status, body) = api_request(&socket, "GET", &format!("/apps/{name}"), "")?;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""status":"running""#));
+
+    let (status, _) = api_request(
+        &socket,
+        "POST",
+        "/apps",
+        &format!(r#"{{"profile": "{name}", "name": "{name}"}}"#),
+    )?;
+    assert_eq!(status, 409);
+
+    let (status, body) = api_request(&socket, "GET", "/apps", "")?;
+    assert_eq!(status, 200);
+    assert!(body.contains(name));
+
+    // Output is streamed as Server-Sent Events until the application stops.
+    let mut events = BufReader::new(UnixStream::connect(&socket)?);
+    write!(
+        events.get_mut(),
+        "GET /apps/{name}/output HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {API_TOKEN}\r\nConnection: close\r\n\r\n"
+    )?;
+
+    let mut line = String::new();
+    events.read_line(&mut line)?;
+    assert!(line.starts_with("HTTP/1.1 200"));
+
+    while line != "\r\n" {
+        line.clear();
+        events.read_line(&mut line)?;
+    }
+
+    let (status, body) = api_request(
+        &socket,
+        "POST",
+        &format!("/apps/{name}/send"),
+        r#"{"command": "ping", "wait": true, "until": "pog"}"#,
+    )?;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""matched":true"#));
+
+    // Supervisor lines are streamed too, like with `log --follow`.
+    while line != "data: pog\n" {
+        line.clear();
+        events.read_line(&mut line)?;
+    }
+
+    let (status, body) = api_request(&socket, "GET", &format!("/apps/{name}/log?lines=50"), "")?;
+    assert_eq!(status, 200);
+    assert!(body.contains("Started long running service"));
+
+    let (status, body) = api_request(&socket, "POST", "/apps/not_this_app/stop", "")?;
+    assert_eq!(status, 404);
+    assert!(body.contains("Application does not exist."));
+
+    let (status, body) = api_request(&socket, "POST", &format!("/apps/{name}/stop"), "")?;
+    assert_eq!(status, 200);
+    assert!(body.contains("Subprocess stopped by the stop command."));
+
+    let mut rest = String::new();
+    events.read_to_string(&mut rest)?;
+    assert!(rest.contains("event: end"));
+
+    api.kill()?;
+    api.wait()?;
+
+    std::fs::remove_file(socket)?;
+    std::fs::remove_file(token_file)?;
+    std::fs::remove_file(profile_path)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn client_long_running_service() -> Result<()> {
+    let name = "client_long_running_service";
+    test_utils::start_long_running_service(name)?;
+
+    let client = crescent::Client::connect(name)?;
+    assert_eq!(client.info()?.name, name);
+
+    let mut output = client.subscribe()?;
+    client.send("ping")?;
+    assert!(output.any(|line| matches!(line.as_deref(), Ok("pog"))));
+
+    assert!(crescent::registry::app_already_running(&name.to_string())?);
+    assert_eq!(client.stop()?, crescent::StopStep::Sigterm);
+
+    // The supervisor exits after the subprocess.
+    crescent::registry::wait_app_exit(&name.to_string(), std::time::Duration::from_secs(5))?;
+
+    let err = crescent::Client::connect(name)
+        .err()
+        .context("Client connected.")?;
+    assert!(matches!(err, crescent::Error::AppNotRunning));
+
+    let err = crescent::Client::connect("client_not_this_app")
+        .err()
+        .context("Client connected.")?;
+    assert!(matches!(err, crescent::Error::AppNotFound));
+
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn stack_up_down_long_running_service() -> Result<()> {
+    let name = "stack_up_down";
+    let backend = "stack_up_down_backend";
+    let proxy = "stack_up_down_proxy";
+
+    let profile_path = test_utils::write_test_profile(name, r#""name": "stack_up_down""#)?;
+
+    let home = env::var("HOME")?;
+    let stacks_dir = PathBuf::from(home).join(".crescent/stacks");
+    std::fs::create_dir_all(&stacks_dir)?;
+
+    // The proxy is listed first but depends on the backend.
+    let stack_path = stacks_dir.join(format!("{name}.json"));
+    std::fs::write(
+        &stack_path,
+        r#"{
+            "apps": [
+                {"name": "stack_up_down_proxy", "profile": "stack_up_down", "depends_on": ["stack_up_down_backend"]},
+                {"name": "stack_up_down_backend", "profile": "stack_up_down", "interpreter_arguments": ["-u"], "application_arguments": ["world one", "-v"]}
+            ]
+        }"#,
+    )?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stack", "up", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Application ready."));
+
+    // Sleeping to make sure the process started
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    assert!(test_utils::check_app_is_running(backend)?);
+    assert!(test_utils::check_app_is_running(proxy)?);
+
+    // Every argument is passed as is, even with spaces.
+    let pids = crescent::registry::app_pids_by_name(&backend.to_string())?;
+    let cmdline = std::fs::read_to_string(format!("/proc/{}/cmdline", pids[1]))?;
+    let cmdline: Vec<&str> = cmdline.trim_end_matches('\0').split('\0').collect();
+    assert_eq!(cmdline[1], "-u");
+    assert_eq!(cmdline[3..], ["world one", "-v"]);
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stack", "status", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Running"))
+        .stdout(predicate::str::contains("Stopped").not());
+
+    // The proxy is stopped before the backend.
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stack", "down", name]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output)?;
+
+    let backend_at = output.find("'stack_up_down_backend'").unwrap();
+    let proxy_at = output.find("'stack_up_down_proxy'").unwrap();
+    assert!(proxy_at < backend_at);
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stack", "status", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Running").not());
+
+    std::fs::remove_file(stack_path)?;
+    std::fs::remove_file(profile_path)?;
+    test_utils::delete_app_folder(backend)?;
+    test_utils::delete_app_folder(proxy)?;
+    Ok(())
+}
+
+#[test]
+fn env_long_running_service() -> Result<()> {
+    let name = "env_long_running";
+    let env_path = env::temp_dir().join("env_long_running.env");
+    std::fs::write(&env_path, "LEVEL=world\nMODE=survival\n")?;
+
+    let profile_path = test_utils::write_test_profile(
+        name,
+        r#""env": {"MODE": "creative", "RCON_PASSWORD": "hunter2"}"#,
+    )?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name, "--wait"]);
+    cmd.args([
+        "--env-file",
+        env_path.to_str().unwrap(),
+        "-e",
+        "LEVEL=nether",
+    ]);
+    cmd.assert().success();
+
+    let send_env = |var: &str, expected: &str| {
+        let mut cmd = test_utils::get_base_command();
+        cmd.args([
+            "send",
+            "--wait",
+            "--until",
+            "=",
+            name,
+            &format!("env {var}"),
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::diff(expected.to_string()));
+    };
+
+    send_env("LEVEL", "LEVEL=nether\n");
+    send_env("MODE", "MODE=creative\n");
+    send_env("RCON_PASSWORD", "RCON_PASSWORD=hunter2\n");
+    send_env("HOME", &format!("HOME={}\n", env::var("HOME")?));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["status", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("nether"))
+        .stdout(predicate::str::contains("********"))
+        .stdout(predicate::str::contains("hunter2").not());
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    // Only the variables set for the application and PATH are left.
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-n", name, "-p", name, "--wait"]);
+    cmd.args(["--clean-env", "--path", "/usr/bin:/bin"]);
+    cmd.assert().success();
+
+    send_env("HOME", "HOME=\n");
+    send_env("PATH", "PATH=/usr/bin:/bin\n");
+    send_env("MODE", "MODE=creative\n");
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    std::fs::remove_file(env_path)?;
+    std::fs::remove_file(profile_path)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn label_long_running_service() -> Result<()> {
+    let names = ["label_service_a", "label_service_b", "label_service_c"];
+
+    for name in names {
+        let mut cmd = test_utils::get_base_command();
+        cmd.args(["start", "./tools/long_running_service.py", "-i", "python3"]);
+        cmd.args(["-n", name, "--wait"]);
+
+        if name != "label_service_c" {
+            cmd.args(["--label", "label_service_backend,label_service_lobby"]);
+        }
+
+        cmd.assert().success();
+    }
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["status", "label_service_a"]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "label_service_backend, label_service_lobby",
+    ));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["send", "--label", "label_service_lobby", "ping"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Command sent.").count(2));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", "--label", "label_service_backend"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("label_service_c").not());
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.arg("list");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("label_service_c"))
+        .stdout(predicate::str::contains("label_service_a").not());
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", "--label", "label_service_backend"]);
+    cmd.assert()
+        .failure()
+        .stderr("Error: No running application has the label 'label_service_backend'.\n");
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", "label_service_c"]);
+    cmd.assert().success();
+
+    for name in names {
+        test_utils::delete_app_folder(name)?;
+    }
+    Ok(())
+}
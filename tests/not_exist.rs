@@ -28,11 +28,21 @@ fn status_no_apps_running() -> Result<()> {
     test_utils::execute_against_app_not_exist(vec!["status", "test_app_not_exist"])
 }
 
+#[test]
+fn history_no_apps_running() -> Result<()> {
+    test_utils::execute_against_app_not_exist(vec!["history", "test_app_not_exist"])
+}
+
 #[test]
 fn stop_no_apps_running() -> Result<()> {
     test_utils::execute_against_app_not_exist(vec!["stop", "test_app_not_exist", "-f"])
 }
 
+#[test]
+fn restart_no_apps_running() -> Result<()> {
+    test_utils::execute_against_app_not_exist(vec!["restart", "test_app_not_exist"])
+}
+
 #[test]
 fn kill_no_apps_running() -> Result<()> {
     test_utils::execute_against_app_not_exist(vec!["kill", "test_app_not_exist"])
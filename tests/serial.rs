@@ -1,4 +1,4 @@
-use std::thread;
+use std::{env, fs, os::unix::fs::PermissionsExt, path::PathBuf, thread};
 
 use anyhow::Result;
 use predicates::prelude::predicate;
@@ -65,3 +65,229 @@ fn start_saved_long_running_service() -> Result<()> {
     test_utils::delete_app_folder(name)?;
     Ok(())
 }
+
+#[test]
+#[serial]
+fn start_saved_env_file_long_running_service() -> Result<()> {
+    let name = "start_saved_env_file";
+    let env_path = env::temp_dir().join("start_saved_env_file.env");
+    fs::write(&env_path, "LEVEL=world\nRCON_PASSWORD=hunter2\n")?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "./tools/long_running_service.py", "-i", "python3"]);
+    cmd.args([
+        "-n",
+        name,
+        "--wait",
+        "--env-file",
+        env_path.to_str().unwrap(),
+    ]);
+    cmd.args(["-e", "LEVEL=nether"]);
+    cmd.assert().success();
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.arg("save");
+    cmd.assert().success();
+
+    // The env file is read again when starting, its secrets are not saved.
+    let save_path = PathBuf::from(env::var("HOME")?).join(".crescent/apps.json");
+    let content = fs::read_to_string(&save_path)?;
+    assert!(content.contains("nether"));
+    assert!(!content.contains("hunter2"));
+    assert_eq!(
+        fs::metadata(&save_path)?.permissions().mode() & 0o777,
+        0o600
+    );
+
+    test_utils::shutdown_long_running_service(name)?;
+    fs::write(&env_path, "LEVEL=world\nRCON_PASSWORD=hunter3\n")?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "--saved"]);
+    cmd.assert().success();
+
+    // Sleeping to make sure the process started
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    for (var, expected) in [("LEVEL", "nether"), ("RCON_PASSWORD", "hunter3")] {
+        let mut cmd = test_utils::get_base_command();
+        cmd.args([
+            "send",
+            "--wait",
+            "--until",
+            "=",
+            name,
+            &format!("env {var}"),
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::diff(format!("{var}={expected}\n")));
+    }
+
+    test_utils::shutdown_long_running_service(name)?;
+    fs::remove_file(env_path)?;
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn daemon_long_running_service() -> Result<()> {
+    let name = "daemon_long_running_service";
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["daemon", "start"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Daemon started."));
+
+    // Started while the daemon runs, supervised from its process.
+    test_utils::start_long_running_service(name)?;
+    assert!(test_utils::check_app_is_running(name)?);
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["daemon", "status"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(name));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["send", "--wait", "--until", "^pog$", name, "ping"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("pog"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["log", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Started long running service"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["daemon", "stop"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Daemon stopped."));
+
+    assert!(!crescent::registry::app_already_running(&name.to_string())?);
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["daemon", "status"]);
+    cmd.assert()
+        .failure()
+        .stderr("Error: The daemon is not running.\n");
+
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn daemon_log_follow_long_running_service() -> Result<()> {
+    let name = "daemon_log_follow_long_running";
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["daemon", "start"]);
+    cmd.assert().success();
+
+    test_utils::start_long_running_service(name)?;
+
+    let mut follow = std::process::Command::new(env!("CARGO_BIN_EXE_cres"))
+        .args(["log", name, "--follow"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+
+    // Waiting for the subscription
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", name]);
+    cmd.assert().success();
+
+    // The daemon keeps running, the subscription is closed when the application stops.
+    let start = std::time::Instant::now();
+
+    while follow.try_wait()?.is_none() && start.elapsed().as_secs() < 10 {
+        thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    if follow.try_wait()?.is_none() {
+        follow.kill()?;
+    }
+
+    let output = follow.wait_with_output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+
+    assert!(output.status.success());
+    assert!(stdout.ends_with(">> Application stopped\n"));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["daemon", "stop"]);
+    cmd.assert().success();
+
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn depends_on_long_running_service() -> Result<()> {
+    let backend = "depends_on_backend";
+    let proxy = "depends_on_proxy";
+
+    let backend_profile =
+        test_utils::write_test_profile(backend, r#""name": "depends_on_backend""#)?;
+    let proxy_profile = test_utils::write_test_profile(
+        proxy,
+        r#""name": "depends_on_proxy", "depends_on": ["depends_on_backend"]"#,
+    )?;
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "-p", proxy]);
+    cmd.assert().failure().stderr(
+        "Error: 'depends_on_proxy' depends on 'depends_on_backend', which is not running.\n",
+    );
+
+    for name in [backend, proxy] {
+        let mut cmd = test_utils::get_base_command();
+        cmd.args(["start", "-p", name, "--wait"]);
+        cmd.assert().success();
+    }
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.arg("save");
+    cmd.assert().success();
+
+    // The proxy is stopped first, even if listed last.
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", "depends_on_backend,depends_on_proxy"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output)?;
+
+    let backend_at = output.find("'depends_on_backend'").unwrap();
+    let proxy_at = output.find("'depends_on_proxy'").unwrap();
+    assert!(proxy_at < backend_at);
+
+    // The backend is started and ready before the proxy.
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "--saved"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Application ready."));
+
+    // Sleeping to make sure the process started
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    assert!(test_utils::check_app_is_running(backend)?);
+    assert!(test_utils::check_app_is_running(proxy)?);
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["stop", "depends_on_*"]);
+    cmd.assert().success();
+
+    std::fs::remove_file(backend_profile)?;
+    std::fs::remove_file(proxy_profile)?;
+    test_utils::delete_app_folder(backend)?;
+    test_utils::delete_app_folder(proxy)?;
+    Ok(())
+}
@@ -1,7 +1,7 @@
-use std::{env, fs, path::PathBuf, str::from_utf8};
+use std::{env, fs, path::PathBuf, thread};
 
-use anyhow::{anyhow, Context, Result};
-use predicates::{prelude::predicate, Predicate};
+use anyhow::{Context, Result};
+use predicates::{boolean::PredicateBooleanExt, prelude::predicate};
 
 #[test]
 fn start_short_lived() -> Result<()> {
@@ -100,18 +100,100 @@ fn log_follow_short_lived() -> Result<()> {
     cmd.args(["log", name, "-f"])
         .timeout(std::time::Duration::from_secs(1));
 
-    let binding = cmd.assert().interrupted();
-    let output = binding.get_output();
+    // The lines already in the log are printed, but there is no supervisor to follow.
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains(">> Printed"))
+        .stdout(predicate::str::contains(">> Watching log").not())
+        .stderr(predicate::str::contains("Application not running."));
+
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn crash_loop_short_lived() -> Result<()> {
+    let name = "crash_loop_short_lived";
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args([
+        "start",
+        "/bin/sh",
+        "-n",
+        name,
+        "-a",
+        "-c",
+        "-a",
+        "sleep 0.2; exit 1",
+        "--restart",
+        "on-failure",
+        "--restart-delay",
+        "0",
+        "--crash-loop-limit",
+        "3",
+    ]);
+    cmd.assert().success();
+
+    // Sleeping to make sure the crash loop was detected
+    thread::sleep(std::time::Duration::from_secs(3));
 
-    let stdout = &output.stdout;
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["status", name]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Failed"));
 
-    let print_predicate = predicate::str::contains(">> Printed");
-    let watching_predicate = predicate::str::contains(">> Watching log");
+    let mut cmd = test_utils::get_base_command();
+    cmd.arg("list");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(name));
 
-    match from_utf8(stdout) {
-        Ok(string) => print_predicate.eval(string) && watching_predicate.eval(string),
-        Err(err) => return Err(anyhow!("{err}")),
-    };
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn history_short_lived() -> Result<()> {
+    let name = "history_short_lived";
+
+    // Echo might exit before the supervisor checks it started, sleeping makes sure a run is recorded.
+    let mut cmd = test_utils::get_base_command();
+    cmd.args([
+        "start",
+        "/bin/sh",
+        "-n",
+        name,
+        "-a",
+        "-c",
+        "-a",
+        "sleep 0.2",
+    ]);
+    cmd.assert().success();
+
+    thread::sleep(std::time::Duration::from_secs(1));
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["history", name]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("code 0"));
+
+    test_utils::delete_app_folder(name)?;
+    Ok(())
+}
+
+#[test]
+fn start_wait_short_lived() -> Result<()> {
+    let name = "start_wait_short_lived";
+
+    let mut cmd = test_utils::get_base_command();
+    cmd.args(["start", "/bin/echo", "-n", name, "--wait"]);
+    // Depending on timing the exit is seen when starting the subprocess or after it.
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("before becoming ready"));
 
     test_utils::delete_app_folder(name)?;
     Ok(())
@@ -1,3 +1,5 @@
+import os
+
 STOP = False
 
 print("Started long running service")
@@ -21,4 +23,8 @@ while not STOP:
     if command == "ping":
         print("pog")
 
+    if command.startswith("env "):
+        name = command[4:]
+        print(f"{name}={os.environ.get(name, '')}")
+
 print("Stopping")